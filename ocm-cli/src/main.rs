@@ -1,17 +1,57 @@
-use std::time::Instant;
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    num::{ParseFloatError, ParseIntError},
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant},
+};
 
 use clap::Parser;
-use ocm_parser::{parse_file, run_output::RunOutput};
+use ocm_parser::{
+    bipartite_graph::BipartiteGraph,
+    compression::{compress_writer, CompressedWriter, Compression},
+    mmap_parser::parse_file_mmap,
+    parse_reader,
+    run_output::RunOutput,
+    solution::{
+        ids_to_indices, indices_to_ids, parse_solution_file, validate_solution, write_solution,
+    },
+};
 use ocm_plotter::plottable::plot_to_file;
 use ocm_solver::{
-    algorithms::{solve, Algorithm},
-    crossings::line_sweep_crossings,
+    algorithms::{
+        barycenter_heuristic::{BarycenterConfig, BarycenterKind},
+        branch_and_bound::BranchAndBoundConfig,
+        exact_dp::{ExactDpConfig, DEFAULT_MAX_FREE_NODES},
+        feedback_arc_set::FeedbackArcSetConfig,
+        median_heuristic::{MedianConfig, MedianKind},
+        memetic::{LocalImprovement, MemeticConfig},
+        portfolio::{PortfolioConfig, DEFAULT_PORTFOLIO},
+        sifting::SiftingConfig,
+        simulated_annealing::{
+            check_cooling_rate, check_initial_temperature, check_moves_per_temperature,
+            AnnealingSchedule, SimulatedAnnealingConfig,
+        },
+        solve_in_place,
+        tabu_search::TabuSearchConfig,
+        Algorithm, FreeLayer, Refinement, SolveOptions, TieBreak,
+    },
+    cancellation::CancellationToken,
+    crossings::count_crossings,
+    events::{EventObserver, SolverEvent},
+    graphs::abscissa_graph::AbscissaGraph,
+    lower_bound::{lower_bound, relative_gap},
+    solver::Solver,
+};
+use signal_hook::{
+    consts::{SIGINT, SIGTERM},
+    flag,
 };
 
 #[derive(Parser, Debug)]
 #[command(author="Thibaut de Saivre", version, about="Solver for the OCM problem", long_about = None)]
 struct Args {
-    /// Graph source file
+    /// Graph source file, or `-` to read from the standard input
     #[arg()]
     source: String,
 
@@ -27,12 +67,122 @@ struct Args {
     #[arg(short, long, value_enum)]
     algorithm: Algorithm,
 
-    /// Save the output to a file
+    /// Layer(s) whose node order may be changed. The top layer is fixed by default (one-sided problem)
+    #[arg(short, long, value_enum, default_value_t = FreeLayer::Bottom)]
+    free_layer: FreeLayer,
+
+    /// Median of the nodes with an even number of neighbors in the median heuristics
+    #[arg(long, value_enum, default_value_t = MedianKind::default())]
+    median_kind: MedianKind,
+
+    /// Weighting of the neighbors in the barycenter heuristics
+    #[arg(long, value_enum, default_value_t = BarycenterKind::default())]
+    barycenter_kind: BarycenterKind,
+
+    /// Order of the nodes with the same median or barycenter. The random order uses `--seed`
+    #[arg(long, value_enum, default_value_t = TieBreak::default())]
+    tie_break: TieBreak,
+
+    /// Maximum number of search nodes explored by the exact solvers
+    #[arg(long)]
+    node_limit: Option<u64>,
+
+    /// Wall-clock time limit in seconds. The solvers stop with the best solution found so far,
+    /// as they do on SIGTERM or SIGINT (a second signal exits immediately)
+    #[arg(long)]
+    time_limit: Option<f64>,
+
+    /// Maximum number of free nodes for the exact-dp algorithm, which falls back to the median heuristic above it
+    #[arg(long, default_value_t = DEFAULT_MAX_FREE_NODES)]
+    dp_max_nodes: usize,
+
+    /// Maximum number of nodes of an independent block for the feedback-arc-set algorithm,
+    /// which falls back to the median heuristic above it
+    #[arg(long, default_value_t = FeedbackArcSetConfig::default().max_nodes)]
+    fas_max_nodes: usize,
+
+    /// Skip the local search improving the order found by the feedback-arc-set algorithm
+    #[arg(long)]
+    fas_no_local_search: bool,
+
+    /// Reduce the instance before solving (isolated nodes, twins and independent blocks),
+    /// and constrain the block solvers with the pairs whose order is forced
+    #[arg(long)]
+    reduce: bool,
+
+    /// Merge the parallel edges into weighted edges before solving (the crossing counts are unchanged)
+    #[arg(long)]
+    merge_parallel_edges: bool,
+
+    /// Split the instance into independent components, solved in parallel
+    #[arg(long)]
+    decompose: bool,
+
+    /// Algorithm building the starting order of the local search algorithms
+    #[arg(long, value_enum)]
+    start: Option<Algorithm>,
+
+    /// Algorithms run in parallel by the portfolio algorithm, separated by commas
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = DEFAULT_PORTFOLIO)]
+    portfolio: Vec<Algorithm>,
+
+    /// Post-optimization applied after the algorithm. The number of removed crossings is printed with `-v`
+    #[arg(long, value_enum)]
+    refinement: Option<Refinement>,
+
+    /// Seed of the random number generator of the randomized algorithms
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Initial temperature of the simulated annealing, positive (chosen from the instance by default)
+    #[arg(long, value_parser = parse_initial_temperature)]
+    initial_temperature: Option<f64>,
+
+    /// Factor applied to the temperature of the simulated annealing after each step, in ]0, 1[
+    #[arg(long, default_value_t = AnnealingSchedule::default().cooling_rate, value_parser = parse_cooling_rate)]
+    cooling_rate: f64,
+
+    /// Number of moves tried at each temperature of the simulated annealing, at least 1
+    /// (the number of free nodes by default)
+    #[arg(long, value_parser = parse_moves_per_temperature)]
+    moves_per_temperature: Option<usize>,
+
+    /// Maximum number of iterations of the tabu search
+    #[arg(long, default_value_t = TabuSearchConfig::default().max_iterations)]
+    tabu_iterations: u64,
+
+    /// Number of iterations during which a node moved by the tabu search cannot be moved again
+    /// (chosen from the number of free nodes by default)
+    #[arg(long)]
+    tabu_tenure: Option<usize>,
+
+    /// Number of individuals kept at each generation of the memetic algorithm
+    #[arg(long, default_value_t = MemeticConfig::default().population_size)]
+    population_size: usize,
+
+    /// Maximum number of generations of the memetic algorithm
+    #[arg(long, default_value_t = MemeticConfig::default().generations)]
+    generations: u64,
+
+    /// Local search applied to each new individual of the memetic algorithm
+    #[arg(long, value_enum, default_value_t = LocalImprovement::default())]
+    local_improvement: LocalImprovement,
+
+    /// Save the output graph to a file, or `-` for the standard output
     #[arg(short, long)]
     output_file: Option<String>,
 
-    /// Save analytics to a file
+    /// Save the bottom layer order to a file in the PACE solution format, or `-` for the standard output.
+    /// Defaults to the standard output when reading from the standard input
     #[arg(short, long)]
+    solution_file: Option<String>,
+
+    /// Start from the bottom layer order of a PACE solution file
+    #[arg(short, long)]
+    initial_solution: Option<String>,
+
+    /// Save analytics to a file
+    #[arg(long)]
     analytics: bool,
 
     /// Plot the result to a file
@@ -40,49 +190,352 @@ struct Args {
     plot: bool,
 }
 
+/// Build the solver of an algorithm, configured from the command line arguments.
+/// `start` builds the starting order of the local search algorithms
+fn build_solver(
+    algorithm: Algorithm,
+    args: &Args,
+    start: Option<Arc<dyn Solver>>,
+) -> Arc<dyn Solver> {
+    match algorithm {
+        Algorithm::Median | Algorithm::IterMedian => Arc::new(MedianConfig {
+            iterated: algorithm == Algorithm::IterMedian,
+            kind: args.median_kind,
+            tie_break: args.tie_break,
+            seed: args.seed,
+        }),
+        Algorithm::Barycenter | Algorithm::IterBarycenter => Arc::new(BarycenterConfig {
+            iterated: algorithm == Algorithm::IterBarycenter,
+            kind: args.barycenter_kind,
+            tie_break: args.tie_break,
+            seed: args.seed,
+        }),
+        Algorithm::BranchAndBound => Arc::new(BranchAndBoundConfig {
+            node_limit: args.node_limit,
+        }),
+        Algorithm::ExactDp => Arc::new(ExactDpConfig {
+            max_nodes: args.dp_max_nodes,
+        }),
+        Algorithm::FeedbackArcSet => Arc::new(FeedbackArcSetConfig {
+            max_nodes: args.fas_max_nodes,
+            local_search: !args.fas_no_local_search,
+        }),
+        Algorithm::Sifting => Arc::new(SiftingConfig { start }),
+        Algorithm::SimulatedAnnealing => Arc::new(SimulatedAnnealingConfig {
+            start,
+            seed: args.seed,
+            schedule: AnnealingSchedule {
+                initial_temperature: args.initial_temperature,
+                cooling_rate: args.cooling_rate,
+                moves_per_temperature: args.moves_per_temperature,
+            },
+        }),
+        Algorithm::TabuSearch => Arc::new(TabuSearchConfig {
+            start,
+            max_iterations: args.tabu_iterations,
+            tenure: args.tabu_tenure,
+        }),
+        Algorithm::Memetic => Arc::new(MemeticConfig {
+            start,
+            population_size: args.population_size,
+            generations: args.generations,
+            seed: args.seed,
+            local_improvement: args.local_improvement,
+        }),
+        Algorithm::Portfolio => Arc::new(PortfolioConfig {
+            solvers: args
+                .portfolio
+                .iter()
+                .map(|&member| match member {
+                    // A portfolio in the portfolio is the default one
+                    Algorithm::Portfolio => member.solver(),
+                    _ => build_solver(member, args, start.clone()),
+                })
+                .collect(),
+        }),
+    }
+}
+
+/// Print the progress and diagnostics of the solvers, prefixed by the name of the solver when there are several
+fn print_event(source: Option<&str>, event: &SolverEvent) {
+    let prefix = source.map_or_else(String::new, |source| format!("[{}] ", source));
+    match event {
+        SolverEvent::Crossings {
+            iteration,
+            crossings,
+        } => eprintln!("{}Iteration {}: {} crossings", prefix, iteration, crossings),
+        SolverEvent::Reverted {
+            iteration,
+            crossings,
+        } => eprintln!(
+            "{}Iteration {} reverted, back to {} crossings",
+            prefix, iteration, crossings
+        ),
+        SolverEvent::MemberFinished {
+            crossings,
+            elapsed_time,
+            status,
+            kept,
+        } => eprintln!(
+            "{}{} crossings in {:?} ({}){}",
+            prefix,
+            crossings,
+            elapsed_time,
+            status,
+            if *kept { ", kept" } else { "" }
+        ),
+        SolverEvent::Finished {
+            iterations,
+            crossings,
+        } => eprintln!(
+            "{}Finished after {} iterations: {} crossings",
+            prefix, iterations, crossings
+        ),
+        SolverEvent::Seed { seed } => eprintln!("{}Seed: {}", prefix, seed),
+        SolverEvent::Fallback { nodes, max_nodes } => eprintln!(
+            "{}{} nodes is more than the limit of {}, falling back to a heuristic",
+            prefix, nodes, max_nodes
+        ),
+        SolverEvent::SparseCrossingMatrix { nodes } => eprintln!(
+            "{}{} free nodes: the crossings of each pair are computed on demand, and each sifting pass takes O(V * E) time",
+            prefix, nodes
+        ),
+        SolverEvent::Reduced {
+            bottom_nodes,
+            reduced_nodes,
+            blocks,
+        } => eprintln!(
+            "{}Reduction: {} bottom nodes reduced to {} nodes in {} blocks",
+            prefix, bottom_nodes, reduced_nodes, blocks
+        ),
+        SolverEvent::ComponentSolved { stats } => eprintln!(
+            "{}{} bottom nodes, {} top nodes, {} edges, {} -> {} crossings in {:?} ({})",
+            prefix,
+            stats.bottom_node_count,
+            stats.top_node_count,
+            stats.edge_count,
+            stats.initial_crossings,
+            stats.final_crossings,
+            stats.elapsed_time,
+            stats.status
+        ),
+        SolverEvent::Refined {
+            refinement,
+            removed,
+        } => eprintln!("{}{}: removed {} crossings", prefix, refinement, removed),
+        SolverEvent::IterationStarted { .. } | SolverEvent::Improved { .. } => {}
+    }
+}
+
+/// Parse the initial temperature of the simulated annealing
+fn parse_initial_temperature(value: &str) -> Result<f64, String> {
+    check_initial_temperature(
+        value
+            .parse()
+            .map_err(|error: ParseFloatError| error.to_string())?,
+    )
+}
+
+/// Parse the cooling rate of the simulated annealing
+fn parse_cooling_rate(value: &str) -> Result<f64, String> {
+    check_cooling_rate(
+        value
+            .parse()
+            .map_err(|error: ParseFloatError| error.to_string())?,
+    )
+}
+
+/// Parse the number of moves per temperature of the simulated annealing
+fn parse_moves_per_temperature(value: &str) -> Result<usize, String> {
+    check_moves_per_temperature(
+        value
+            .parse()
+            .map_err(|error: ParseIntError| error.to_string())?,
+    )
+}
+
+/// Name of the standard input and output streams on the command line
+const STDIO: &str = "-";
+
+/// Open a file for writing (compressed according to its extension), or the standard output.
+/// The writer must be finalized with [`CompressedWriter::finish`]
+fn create_writer(filename: &str) -> io::Result<CompressedWriter<Box<dyn Write>>> {
+    if filename == STDIO {
+        return compress_writer(Box::new(io::stdout().lock()), Compression::None);
+    }
+    compress_writer(
+        Box::new(BufWriter::new(File::create(filename)?)),
+        Compression::from_extension(filename),
+    )
+}
+
+/// Write an output file (or stdout) with a writer function.
+/// Errors are reported on stderr, and exit the program with a non-zero status
+fn write_output<F>(filename: &str, write: F)
+where
+    F: FnOnce(&mut CompressedWriter<Box<dyn Write>>) -> io::Result<()>,
+{
+    let result = create_writer(filename).and_then(|mut writer| {
+        write(&mut writer)?;
+        writer.finish().map(|_| ())
+    });
+    if let Err(error) = result {
+        eprintln!("Could not write {}: {}", filename, error);
+        std::process::exit(1);
+    }
+}
+
+impl Args {
+    /// The file the bottom layer order is saved to, if any.
+    /// The CLI writes the solution to the standard output when used as a filter
+    fn solution_file(&self) -> Option<&str> {
+        match &self.solution_file {
+            Some(solution_file) => Some(solution_file),
+            None if self.source == STDIO => Some(STDIO),
+            None => None,
+        }
+    }
+}
+
+// Diagnostics are printed to stderr, so that stdout can be used for the solution when using the CLI as a filter
 fn main() {
     let args = Args::parse();
 
+    // The graph and the solution cannot both be written to the standard output
+    if args.output_file.as_deref() == Some(STDIO) && args.solution_file() == Some(STDIO) {
+        eprintln!(
+            "The output graph and the solution cannot both be written to the standard output, use --solution-file to write the solution to a file"
+        );
+        std::process::exit(2);
+    }
+
     if args.debug {
-        println!("Reading graph from file {}", args.source);
+        eprintln!("Reading graph from file {}", args.source);
     }
 
-    let graph = parse_file(&args.source);
+    let graph = if args.source == STDIO {
+        parse_reader(io::stdin().lock())
+    } else {
+        parse_file_mmap(&args.source)
+    };
+    let mut graph = graph.unwrap_or_else(|error| {
+        eprintln!("Could not parse {}: {}", args.source, error);
+        std::process::exit(1);
+    });
+    if args.merge_parallel_edges {
+        let edge_count = graph.edges.len();
+        graph.merge_parallel_edges();
+        if args.verbose {
+            eprintln!(
+                "Merged the parallel edges: {} -> {} edges",
+                edge_count,
+                graph.edges.len()
+            );
+        }
+    }
 
     if args.debug {
-        println!("Graph read from file: {:?}", graph);
+        eprintln!("Graph read from file: {:?}", graph);
+    }
+
+    // Reorder the bottom layer if a starting solution is given
+    let mut solution_graph: AbscissaGraph = (&graph).into();
+    if let Some(initial_solution) = &args.initial_solution {
+        let bottom_order = parse_solution_file(initial_solution)
+            .and_then(|bottom_order| validate_solution(&graph, &bottom_order).map(|_| bottom_order))
+            .unwrap_or_else(|error| {
+                eprintln!("Invalid initial solution {}: {}", initial_solution, error);
+                std::process::exit(1);
+            });
+        solution_graph.set_bottom_order(&ids_to_indices(graph.top_node_count, &bottom_order));
     }
 
     let mut initial_crossings = 0_u64;
 
     // Lazy evaluation
     if args.verbose || args.analytics {
-        initial_crossings = line_sweep_crossings(&graph);
+        initial_crossings = count_crossings(&solution_graph);
     }
 
+    let start = args.start.map(|start| build_solver(start, &args, None));
+    let solver = build_solver(args.algorithm, &args, start);
+
     if args.verbose {
-        println!("Crossings before: {}", initial_crossings);
-        println!("Using algorithm: {:?}", args.algorithm);
+        eprintln!("Crossings before: {}", initial_crossings);
+        let parameters: Vec<String> = solver
+            .parameters()
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        if parameters.is_empty() {
+            eprintln!("Using algorithm: {}", solver.name());
+        } else {
+            eprintln!(
+                "Using algorithm: {} ({})",
+                solver.name(),
+                parameters.join(", ")
+            );
+        }
     }
 
+    // Stop the solver on SIGTERM or SIGINT, and still write the best solution found so far.
+    // A second signal terminates the process without waiting for the solver
+    let time_limit = args.time_limit.map(Duration::from_secs_f64);
+    let cancellation = CancellationToken::new().with_time_limit(time_limit);
+    let interrupted = cancellation.flag();
+    for signal in [SIGTERM, SIGINT] {
+        flag::register_conditional_shutdown(signal, 1, cancellation.flag()).unwrap();
+        flag::register(signal, cancellation.flag()).unwrap();
+    }
+
+    let options = SolveOptions {
+        free_layer: args.free_layer,
+        time_limit,
+        cancellation,
+        reduce: args.reduce,
+        precedence: None,
+        decompose: args.decompose,
+        refinement: args.refinement,
+        observer: if args.verbose {
+            EventObserver::new(print_event)
+        } else {
+            EventObserver::default()
+        },
+    };
+
     let start_time = Instant::now();
-    let graph = solve(&graph, &args.algorithm, args.verbose);
+    let stats = solve_in_place(&mut solution_graph, &*solver, &options);
     let elapsed_time = start_time.elapsed();
+    if args.verbose && interrupted.load(Ordering::Relaxed) {
+        eprintln!("Interrupted, keeping the best solution found so far");
+    }
+    let graph: BipartiteGraph = (&solution_graph).into();
 
     let mut final_crossings = 0_u64;
+    let mut crossings_lower_bound = 0_u64;
 
     // Lazy evaluation
     if args.verbose || args.analytics {
-        final_crossings = line_sweep_crossings(&graph);
+        final_crossings = count_crossings(&graph);
+        crossings_lower_bound = lower_bound(&solution_graph, args.free_layer);
     }
+    let gap = relative_gap(final_crossings, crossings_lower_bound);
     if args.verbose {
-        println!("Crossings after: {}", final_crossings);
+        eprintln!(
+            "Crossings after: {} ({}, {} iterations)",
+            final_crossings, stats.status, stats.iterations
+        );
+        eprintln!(
+            "Lower bound: {} (gap {:.2}%)",
+            crossings_lower_bound,
+            100.0 * gap
+        );
     }
 
     // Print elapsed time if the flag is set
     if args.verbose {
         // Print the elapsed time in seconds and milliseconds
-        println!(
+        eprintln!(
             "Elapsed time: {}.{} seconds",
             elapsed_time.as_secs(),
             elapsed_time.subsec_nanos()
@@ -96,22 +549,45 @@ fn main() {
 
     if args.analytics {
         // Save the analytics to a file
-        let parts: Vec<&str> = args.source.split('/').collect();
+        // The dataset is the parent directory of the source file
+        let dataset = args.source.split('/').nth_back(1).unwrap_or("stdin");
 
         let run_output = RunOutput::new(
             &args.source,
-            &args.algorithm.to_string(),
-            parts[parts.len() - 2],
+            solver.name(),
+            dataset,
             initial_crossings,
             final_crossings,
             elapsed_time.as_nanos() as u64,
+            crossings_lower_bound,
+            gap,
         );
         run_output.save_to_file();
     }
 
     // Save the output to a file if the flag is set
-    if let Some(output_file) = args.output_file {
+    if let Some(output_file) = &args.output_file {
         // Save the graph to a file
-        graph.save_to_file(&output_file).unwrap();
+        write_output(output_file, |writer| graph.write(writer));
+    }
+
+    // Save the bottom layer order if the flag is set, or if the CLI is used as a filter
+    if let Some(solution_file) = args.solution_file() {
+        let bottom_order = indices_to_ids(graph.top_node_count, &solution_graph.bottom_order());
+        write_output(solution_file, |writer| {
+            write_solution(writer, &bottom_order)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn verify_args() {
+        Args::command().debug_assert();
     }
 }
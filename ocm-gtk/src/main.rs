@@ -6,8 +6,9 @@ use crate::gtk_utils::plot_in_window;
 use clap::Parser;
 use ocm_parser::bipartite_graph::BipartiteGraph;
 use ocm_parser::parse_file;
-use ocm_solver::algorithms::{solve, Algorithm};
-use ocm_solver::crossings::line_sweep_crossings;
+use ocm_solver::algorithms::{solve, Algorithm, FreeLayer, SolveOptions};
+use ocm_solver::crossings::count_crossings;
+use ocm_solver::events::{EventObserver, SolverEvent};
 
 mod gtk_utils;
 mod plotter_widget;
@@ -31,6 +32,10 @@ struct Args {
     #[arg(short, long, value_enum)]
     algorithm: Algorithm,
 
+    /// Layer(s) whose node order may be changed. The top layer is fixed by default (one-sided problem)
+    #[arg(short, long, value_enum, default_value_t = FreeLayer::Bottom)]
+    free_layer: FreeLayer,
+
     /// Save the output to a file
     #[arg(short, long)]
     output_file: Option<String>,
@@ -47,7 +52,10 @@ fn main() {
         println!("Reading graph from file {}", args.source);
     }
 
-    let graph = parse_file(&args.source);
+    let graph = parse_file(&args.source).unwrap_or_else(|error| {
+        eprintln!("Could not parse {}: {}", args.source, error);
+        std::process::exit(1);
+    });
 
     if args.debug {
         println!("Graph read from file: {:?}", graph);
@@ -60,15 +68,38 @@ fn main() {
     if args.verbose {
         println!(
             "Crossings before: {}",
-            line_sweep_crossings::<BipartiteGraph>(&graph_rc.borrow())
+            count_crossings::<BipartiteGraph>(&graph_rc.borrow())
         );
         println!("Using algorithm: {:?}", args.algorithm);
     }
 
-    let graph = solve(&graph_rc.borrow(), &args.algorithm, args.verbose);
+    let options = SolveOptions {
+        free_layer: args.free_layer,
+        observer: if args.verbose {
+            // Progress is reported on stderr, as the window only shows the final result
+            EventObserver::new(|_: Option<&str>, event: &SolverEvent| match event {
+                SolverEvent::Crossings {
+                    iteration,
+                    crossings,
+                } => eprintln!("Iteration {}: {} crossings", iteration, crossings),
+                SolverEvent::Improved {
+                    iteration,
+                    crossings,
+                } => eprintln!(
+                    "Iteration {}: improved to {} crossings",
+                    iteration, crossings
+                ),
+                _ => {}
+            })
+        } else {
+            EventObserver::default()
+        },
+        ..Default::default()
+    };
+    let graph = solve(&graph_rc.borrow(), &args.algorithm, &options);
 
     if args.verbose {
-        println!("Crossings after: {}", line_sweep_crossings(&graph));
+        println!("Crossings after: {}", count_crossings(&graph));
     }
 
     // Print elapsed time if the flag is set
@@ -92,5 +123,6 @@ fn main() {
         // Save the resulting graph to a file
         let filename = args.output_file.unwrap();
         graph_rc.borrow().save_to_file(&filename).unwrap();
+        eprintln!("Graph saved to file {}", filename);
     }
 }
@@ -2,7 +2,10 @@
 
 use std::io::Write;
 
-use crate::graph_base::{Edge, OrderedGraph};
+use crate::{
+    compression::create_file,
+    graph_base::{Edge, OrderedGraph, WeightedEdge},
+};
 /// Bipartite Graph data structure, as parsed from the dataset files
 #[derive(Debug, Default, Clone)]
 pub struct BipartiteGraph {
@@ -11,8 +14,11 @@ pub struct BipartiteGraph {
     /// Number of bottom nodes. Their indices start from `top_node_count + 1`
     pub bottom_node_count: u64,
 
-    /// Edges between the top and bottom nodes
+    /// Edges between the top and bottom nodes. Parallel edges are allowed
     pub edges: Vec<Edge>,
+    /// Weight of each edge, in the same order as the edges.
+    /// Empty if the graph is unweighted, i.e. if all edges have a weight of 1
+    pub weights: Vec<u64>,
 }
 
 impl BipartiteGraph {
@@ -21,10 +27,86 @@ impl BipartiteGraph {
         Self::default()
     }
 
+    /// Whether some edges have a weight other than 1
+    pub fn is_weighted(&self) -> bool {
+        !self.weights.is_empty()
+    }
+
+    /// Weight of the edge at the given index
+    pub fn weight(&self, index: usize) -> u64 {
+        if self.weights.is_empty() {
+            1
+        } else {
+            self.weights[index]
+        }
+    }
+
+    /// Append an edge with its weight. The weights are only stored once an edge has a weight other than 1
+    pub fn push_edge(&mut self, edge: Edge, weight: u64) {
+        if weight != 1 || self.is_weighted() {
+            self.weights.resize(self.edges.len(), 1);
+            self.weights.push(weight);
+        }
+        self.edges.push(edge);
+    }
+
+    /// Append edges with their weights, which may be empty if all of them have a weight of 1
+    pub fn extend_edges(&mut self, edges: Vec<Edge>, weights: Vec<u64>) {
+        if !weights.is_empty() || self.is_weighted() {
+            self.weights.resize(self.edges.len(), 1);
+            if weights.is_empty() {
+                self.weights.resize(self.edges.len() + edges.len(), 1);
+            } else {
+                self.weights.extend(weights);
+            }
+        }
+        self.edges.extend(edges);
+    }
+
+    /// Merge the parallel edges into a single edge whose weight is the sum of their weights, saturating at `u64::MAX`.
+    /// The crossing count of any node order is unchanged. The edges are sorted in lexicographic order.
+    ///
+    /// Complexity
+    /// ----------
+    /// * Time: `O(E * log(E))`
+    /// * Space: `O(E)`
+    pub fn merge_parallel_edges(&mut self) {
+        let mut edges: Vec<WeightedEdge> = (0..self.edges.len())
+            .map(|index| (self.edges[index].0, self.edges[index].1, self.weight(index)))
+            .collect();
+        edges.sort_unstable();
+
+        let mut merged: Vec<WeightedEdge> = Vec::with_capacity(edges.len());
+        for (top, bottom, weight) in edges {
+            match merged.last_mut() {
+                Some(last) if (last.0, last.1) == (top, bottom) => {
+                    last.2 = last.2.saturating_add(weight)
+                }
+                _ => merged.push((top, bottom, weight)),
+            }
+        }
+
+        self.edges.clear();
+        self.weights.clear();
+        for (top, bottom, weight) in merged {
+            self.push_edge((top, bottom), weight);
+        }
+    }
+
+    /// Save the graph to a file, in the same format as the dataset files.
+    /// The file is compressed if its extension is `.gz`, `.zst` or `.xz`.
     pub fn save_to_file(&self, filename: &str) -> std::io::Result<()> {
-        let mut file = std::fs::File::create(filename)?;
-        let mut writer = std::io::BufWriter::new(&mut file);
+        let mut writer = create_file(filename)?;
 
+        self.write(&mut writer)?;
+        writer.finish()?; // Finish the compressed stream before reporting success
+
+        Ok(())
+    }
+
+    /// Write the graph to any writer, in the same format as the dataset files.
+    /// The weights of weighted graphs are written after each edge
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
         // Write the header
         writeln!(
             writer,
@@ -35,13 +117,15 @@ impl BipartiteGraph {
         )?;
 
         // Write the edges
-        for (top, bottom) in &self.edges {
-            writeln!(writer, "{} {}", top, bottom)?;
+        for (index, (top, bottom)) in self.edges.iter().enumerate() {
+            if self.is_weighted() {
+                writeln!(writer, "{} {} {}", top, bottom, self.weights[index])?;
+            } else {
+                writeln!(writer, "{} {}", top, bottom)?;
+            }
         }
 
-        println!("Graph saved to file {}", filename);
-
-        Ok(())
+        writer.flush()
     }
 }
 
@@ -56,4 +140,12 @@ impl OrderedGraph for BipartiteGraph {
             .map(|(top, bottom)| (top - 1, bottom - 1 - self.top_node_count))
             .collect()
     }
+
+    fn get_ordered_weighted_edges(&self) -> Vec<WeightedEdge> {
+        self.get_ordered_edges()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (top, bottom))| (top, bottom, self.weight(index)))
+            .collect()
+    }
 }
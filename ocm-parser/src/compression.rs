@@ -0,0 +1,180 @@
+//! Transparent compression support for graph and solution files.
+//!
+//! Compressed inputs are detected from their magic bytes, so that they can also be read from the standard input.
+//! The compression of output files is chosen from their extension (`.gz`, `.zst` or `.xz`).
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use flate2::{bufread::MultiGzDecoder, write::GzEncoder};
+use xz2::{bufread::XzDecoder, write::XzEncoder};
+
+/// Gzip magic bytes
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+/// Zstandard magic bytes
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+/// XZ magic bytes
+const XZ_MAGIC: &[u8] = &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00];
+
+/// Compression formats supported for graph and solution files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    /// Guess the compression format from a file extension
+    pub fn from_extension<P: AsRef<Path>>(path: P) -> Self {
+        match path.as_ref().extension().and_then(|ext| ext.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") | Some("zstd") => Compression::Zstd,
+            Some("xz") => Compression::Xz,
+            _ => Compression::None,
+        }
+    }
+
+    /// Detect the compression format from the first bytes of a stream
+    pub fn from_magic(bytes: &[u8]) -> Self {
+        if bytes.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if bytes.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if bytes.starts_with(XZ_MAGIC) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+}
+
+/// Wrap a reader so that its content is transparently decompressed if it starts with known magic bytes.
+pub fn decompress_reader<'a, R: BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    // Peek at the first bytes without consuming them
+    let compression = Compression::from_magic(reader.fill_buf()?);
+
+    Ok(match compression {
+        Compression::None => Box::new(reader),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        Compression::Xz => Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader))),
+    })
+}
+
+/// Writer compressing its content with one of the supported formats.
+///
+/// Call [`CompressedWriter::finish`] once everything is written: dropping the writer finalizes the stream
+/// on a best effort basis, and silently ignores the errors (a full disk would leave a truncated file).
+pub enum CompressedWriter<W: Write> {
+    None(W),
+    Gzip(GzEncoder<W>),
+    Zstd(zstd::Encoder<'static, W>),
+    Xz(XzEncoder<W>),
+}
+
+impl<W: Write> CompressedWriter<W> {
+    /// Finalize the compressed stream and flush the inner writer, reporting any error.
+    /// Returns the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        let mut writer = match self {
+            CompressedWriter::None(writer) => writer,
+            CompressedWriter::Gzip(encoder) => encoder.finish()?,
+            CompressedWriter::Zstd(encoder) => encoder.finish()?,
+            CompressedWriter::Xz(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            CompressedWriter::None(writer) => writer.write(buf),
+            CompressedWriter::Gzip(encoder) => encoder.write(buf),
+            CompressedWriter::Zstd(encoder) => encoder.write(buf),
+            CompressedWriter::Xz(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            CompressedWriter::None(writer) => writer.flush(),
+            CompressedWriter::Gzip(encoder) => encoder.flush(),
+            CompressedWriter::Zstd(encoder) => encoder.flush(),
+            CompressedWriter::Xz(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Wrap a writer so that its content is compressed with the given format.
+/// The compressed stream must be finalized with [`CompressedWriter::finish`].
+pub fn compress_writer<W: Write>(
+    writer: W,
+    compression: Compression,
+) -> io::Result<CompressedWriter<W>> {
+    Ok(match compression {
+        Compression::None => CompressedWriter::None(writer),
+        Compression::Gzip => {
+            CompressedWriter::Gzip(GzEncoder::new(writer, flate2::Compression::default()))
+        }
+        Compression::Zstd => CompressedWriter::Zstd(zstd::Encoder::new(writer, 0)?),
+        Compression::Xz => CompressedWriter::Xz(XzEncoder::new(writer, 6)),
+    })
+}
+
+/// Open a file for reading, transparently decompressing it if needed
+pub fn open_file(filename: &str) -> io::Result<Box<dyn BufRead>> {
+    decompress_reader(BufReader::new(File::open(filename)?))
+}
+
+/// Create a file for writing, compressing it according to its extension.
+/// The compressed stream must be finalized with [`CompressedWriter::finish`].
+pub fn create_file(filename: &str) -> io::Result<CompressedWriter<BufWriter<File>>> {
+    compress_writer(
+        BufWriter::new(File::create(filename)?),
+        Compression::from_extension(filename),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn compression_round_trip() {
+        let content = b"p ocr 2 2 1\n1 3\n";
+
+        for compression in [
+            Compression::None,
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Xz,
+        ] {
+            let mut writer = compress_writer(Vec::new(), compression).unwrap();
+            writer.write_all(content).unwrap();
+            let compressed = writer.finish().unwrap();
+            assert_eq!(Compression::from_magic(&compressed), compression);
+
+            let mut decompressed = Vec::new();
+            decompress_reader(&compressed[..])
+                .unwrap()
+                .read_to_end(&mut decompressed)
+                .unwrap();
+            assert_eq!(decompressed, content, "{:?} round trip failed", compression);
+        }
+
+        assert_eq!(Compression::from_extension("1.gr.gz"), Compression::Gzip);
+        assert_eq!(Compression::from_extension("1.gr.zst"), Compression::Zstd);
+        assert_eq!(Compression::from_extension("1.gr.xz"), Compression::Xz);
+        assert_eq!(Compression::from_extension("1.gr"), Compression::None);
+    }
+}
@@ -0,0 +1,115 @@
+//! Error type returned when parsing graph files
+
+use std::fmt::{Display, Formatter};
+
+use crate::graph_base::MAX_WEIGHT;
+
+/// What went wrong while parsing a graph file
+#[derive(Debug)]
+pub enum ParseErrorKind {
+    /// The underlying reader failed
+    Io(std::io::Error),
+    /// The file contains no `p ocr` header line
+    MissingHeader,
+    /// The header line does not follow the `p ocr <top> <bottom> <edges> [<cutwidth>]` format
+    InvalidHeader,
+    /// A cutwidth ordering line does not contain a single vertex index
+    InvalidOrdering,
+    /// An edge line does not follow the `<top> <bottom> [<weight>]` format
+    InvalidEdge,
+    /// An edge has a weight of 0, or greater than [`MAX_WEIGHT`]
+    InvalidWeight,
+    /// A vertex index is not within the range announced by the header
+    VertexOutOfRange { vertex: u64, min: u64, max: u64 },
+    /// The file ends before the cutwidth ordering announced by the header is complete
+    MissingOrdering { expected: u64, found: u64 },
+    /// The file contains fewer edges than announced by the header
+    MissingEdges { expected: u64, found: u64 },
+    /// The file contains more lines than announced by the header
+    UnexpectedLine,
+}
+
+/// Error returned when a graph file cannot be parsed.
+/// Lines and columns are 1-based, and are 0 when the error is not tied to a position in the file.
+#[derive(Debug)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub line: usize,
+    pub column: usize,
+    /// Offending line content
+    pub text: String,
+}
+
+impl ParseError {
+    /// Create a new error located in the file
+    pub fn new(kind: ParseErrorKind, line: usize, column: usize, text: &str) -> Self {
+        ParseError {
+            kind,
+            line,
+            column,
+            text: text.to_string(),
+        }
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseErrorKind::Io(error) => write!(f, "I/O error: {}", error),
+            ParseErrorKind::MissingHeader => write!(f, "missing \"p ocr\" header line"),
+            ParseErrorKind::InvalidHeader => write!(
+                f,
+                "invalid header, expected \"p ocr <top> <bottom> <edges> [<cutwidth>]\""
+            ),
+            ParseErrorKind::InvalidOrdering => {
+                write!(f, "invalid ordering line, expected a single vertex index")
+            }
+            ParseErrorKind::InvalidEdge => {
+                write!(f, "invalid edge, expected \"<top> <bottom> [<weight>]\"")
+            }
+            ParseErrorKind::InvalidWeight => {
+                write!(f, "edge weights must be in [1, {}]", MAX_WEIGHT)
+            }
+            ParseErrorKind::VertexOutOfRange { vertex, min, max } => write!(
+                f,
+                "vertex {} is out of range, expected an index in [{}, {}]",
+                vertex, min, max
+            ),
+            ParseErrorKind::MissingOrdering { expected, found } => {
+                write!(f, "expected {} ordering lines, found {}", expected, found)
+            }
+            ParseErrorKind::MissingEdges { expected, found } => {
+                write!(f, "expected {} edges, found {}", expected, found)
+            }
+            ParseErrorKind::UnexpectedLine => write!(f, "unexpected line after the last edge"),
+        }
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.line == 0 {
+            return write!(f, "{}", self.kind);
+        }
+        write!(
+            f,
+            "line {}, column {}: {}: \"{}\"",
+            self.line, self.column, self.kind, self.text
+        )
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ParseErrorKind::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ParseError {
+    fn from(error: std::io::Error) -> Self {
+        ParseError::new(ParseErrorKind::Io(error), 0, 0, "")
+    }
+}
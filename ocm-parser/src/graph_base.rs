@@ -3,6 +3,14 @@
 
 pub type Edge = (u64, u64);
 
+/// Edge with its weight `(top, bottom, weight)`. An edge of weight `w` crosses other edges like `w` parallel edges,
+/// so the crossing between two weighted edges counts as the product of their weights.
+pub type WeightedEdge = (u64, u64, u64);
+
+/// Largest edge weight accepted by the parsers. The crossing between two edges counts at most `2^32`,
+/// so that the crossing counts of large graphs, and the differences between them, still fit in an `i64`
+pub const MAX_WEIGHT: u64 = u16::MAX as u64;
+
 /// Trait for graphs that can return their edges with the following conventions:
 /// - Edges refer to nodes by their indices, starting from 0.
 /// - The first top node has an index of 0.
@@ -10,4 +18,12 @@ pub type Edge = (u64, u64);
 /// - The nodes are ordered by their indices.
 pub trait OrderedGraph {
     fn get_ordered_edges(&self) -> Vec<Edge>;
+
+    /// Same as [`OrderedGraph::get_ordered_edges`], with the weight of each edge (1 for unweighted graphs)
+    fn get_ordered_weighted_edges(&self) -> Vec<WeightedEdge> {
+        self.get_ordered_edges()
+            .into_iter()
+            .map(|(top, bottom)| (top, bottom, 1))
+            .collect()
+    }
 }
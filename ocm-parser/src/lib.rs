@@ -1,38 +1,147 @@
 use std::io::BufRead;
 
 use bipartite_graph::BipartiteGraph;
-use parser::{parse_graph_edges, parse_graph_header};
+use compression::decompress_reader;
+use error::ParseError;
+use parser::LineParser;
 
 pub mod bipartite_graph;
+pub mod compression;
+pub mod error;
 pub mod graph_base;
+pub mod mmap_parser;
 pub mod parser;
 pub mod run_output;
+pub mod solution;
 
-/// Parse a graph from a source file
-pub fn parse_file(filename: &str) -> BipartiteGraph {
-    // Read the file line by line
-    let file = std::fs::File::open(filename).unwrap();
+/// Parse a graph from a source file. Compressed files (gzip, zstd, xz) are decompressed on the fly.
+pub fn parse_file(filename: &str) -> Result<BipartiteGraph, ParseError> {
+    let file = std::fs::File::open(filename)?;
 
-    let reader = std::io::BufReader::new(file);
-    let mut lines = reader.lines();
+    parse_reader(std::io::BufReader::new(file))
+}
+
+/// Parse a graph from an in-memory string
+pub fn parse_str(content: &str) -> Result<BipartiteGraph, ParseError> {
+    parse_reader(content.as_bytes())
+}
 
-    let mut graph = BipartiteGraph::new(); // Create an empty graph
+/// Parse a graph from any buffered source (file, standard input, network stream...), line by line.
+/// Compressed sources (gzip, zstd, xz) are detected from their magic bytes and decompressed on the fly.
+pub fn parse_reader<R: BufRead>(reader: R) -> Result<BipartiteGraph, ParseError> {
+    let reader = decompress_reader(reader)?;
+    let mut parser = LineParser::new();
 
-    // Parse the header line
-    let header_line = lines.next().unwrap().unwrap();
-    let mut header_str = header_line.as_str();
-    let (top_count, bot_count, edge_count) = parse_graph_header(&mut header_str).unwrap();
-    graph.top_node_count = top_count;
-    graph.bottom_node_count = bot_count;
+    for (index, line) in reader.lines().enumerate() {
+        parser.parse_line(&line?, index + 1)?;
+    }
+
+    parser.finish()
+}
 
-    // Parse the edges
-    for _ in 0..edge_count {
-        let edge_line = lines.next().unwrap().unwrap();
-        let mut edge_str = edge_line.as_str();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ParseErrorKind;
 
-        let (top, bot) = parse_graph_edges(&mut edge_str).unwrap();
-        graph.edges.push((top, bot));
+    #[test]
+    fn parse_full_grammar() {
+        let graph = parse_str(
+            "c leading comment\r\n\
+             p  ocr\t2 2 3 1 \r\n\
+             \n\
+             1\n2\n3\n4\n\
+             c edges\n\
+             1 3\n  2\t4  \r\n\
+             2 3\n",
+        )
+        .unwrap();
+
+        assert_eq!(graph.top_node_count, 2);
+        assert_eq!(graph.bottom_node_count, 2);
+        assert_eq!(graph.edges, vec![(1, 3), (2, 4), (2, 3)]);
     }
 
-    graph
+    #[test]
+    fn parse_weighted_edges() {
+        let mut graph = parse_str("p ocr 2 2 4\n1 3\n2 4 3\n1 3 2\n2 3\n").unwrap();
+        assert_eq!(graph.edges, vec![(1, 3), (2, 4), (1, 3), (2, 3)]);
+        assert_eq!(graph.weights, vec![1, 3, 2, 1]);
+
+        graph.merge_parallel_edges();
+        assert_eq!(graph.edges, vec![(1, 3), (2, 3), (2, 4)]);
+        assert_eq!(graph.weights, vec![3, 1, 3]);
+
+        let mut buffer = Vec::new();
+        graph.write(&mut buffer).unwrap();
+        assert_eq!(buffer, b"p ocr 2 2 3\n1 3 3\n2 3 1\n2 4 3\n");
+        assert_eq!(parse_reader(&buffer[..]).unwrap().weights, graph.weights);
+
+        // Unweighted graphs do not store their weights
+        let graph = parse_str("p ocr 2 2 2\n1 3 1\n2 4\n").unwrap();
+        assert!(!graph.is_weighted());
+        assert_eq!(graph.weight(1), 1);
+
+        let error = parse_str("p ocr 2 2 1\n1 3  0\n").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::InvalidWeight));
+        assert_eq!((error.line, error.column), (2, 6));
+        let error = parse_str("p ocr 2 2 1\n1 3 65536\n").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::InvalidWeight));
+        assert_eq!(
+            parse_str("p ocr 2 2 1\n1 3 65535\n").unwrap().weights,
+            vec![65535]
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        // Hostile headers are rejected or parsed without trusting their counts
+        let error = parse_str("p ocr 18446744073709551615 1 0\n").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::InvalidHeader));
+        let error = parse_str("p ocr 1 1 99999999999999\n").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::MissingEdges { .. }));
+
+        let error = parse_str("p ocr 2 2 1\n1 x\n").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::InvalidEdge));
+        assert_eq!((error.line, error.column), (2, 3));
+        assert_eq!(error.text, "1 x");
+
+        let error = parse_str("p ocr 2 2 1\n  1 5\n").unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::VertexOutOfRange {
+                vertex: 5,
+                min: 3,
+                max: 4
+            }
+        ));
+        assert_eq!((error.line, error.column), (2, 5));
+
+        let error = parse_str("p ocr 2 2 2\n1 3\n").unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::MissingEdges {
+                expected: 2,
+                found: 1
+            }
+        ));
+
+        let error = parse_str("p ocr 2 2 1 1\n1\n2\n").unwrap_err();
+        assert!(matches!(
+            error.kind,
+            ParseErrorKind::MissingOrdering {
+                expected: 4,
+                found: 2
+            }
+        ));
+
+        let error = parse_str("p ocr 2 2 1\n1 3\n2 4\n").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::UnexpectedLine));
+
+        let error = parse_str("p tw 2 2 1\n").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::InvalidHeader));
+
+        let error = parse_str("c only comments\n").unwrap_err();
+        assert!(matches!(error.kind, ParseErrorKind::MissingHeader));
+    }
 }
@@ -0,0 +1,306 @@
+//! Fast memory-mapped parser for very large graph files.
+//!
+//! The header is parsed sequentially, then the edge section is split into chunks at line boundaries,
+//! which are parsed in parallel directly from the mapped bytes (no per-line allocation).
+//! When a chunk contains an invalid line, it is parsed again sequentially with the regular line parser,
+//! so that the reported errors are exactly the same as with [`parse_file`](crate::parse_file).
+
+use std::{borrow::Cow, fs::File, io::BufReader, ops::Range};
+
+use memmap2::Mmap;
+use rayon::prelude::*;
+
+use crate::{
+    bipartite_graph::BipartiteGraph,
+    compression::Compression,
+    error::ParseError,
+    graph_base::{Edge, MAX_WEIGHT},
+    parse_reader,
+    parser::LineParser,
+};
+
+/// Minimum size in bytes of the edge chunks parsed in parallel
+const MIN_CHUNK_SIZE: usize = 1 << 16;
+
+/// Parse a graph from a source file by mapping it in memory and parsing its edges in parallel.
+///
+/// Compressed files and special files (pipes, character devices...) cannot be mapped,
+/// and are handed over to the streaming parser instead.
+pub fn parse_file_mmap(filename: &str) -> Result<BipartiteGraph, ParseError> {
+    let file = File::open(filename)?;
+    if !file.metadata()?.is_file() {
+        return parse_reader(BufReader::new(file));
+    }
+
+    // Safety: the mapped file must not be modified by another process while it is being parsed.
+    let mmap = unsafe { Mmap::map(&file)? };
+    if Compression::from_magic(&mmap) != Compression::None {
+        return parse_reader(BufReader::new(file));
+    }
+
+    parse_bytes(&mmap)
+}
+
+/// Parse a graph from an in-memory buffer, parsing its edges in parallel
+pub fn parse_bytes(bytes: &[u8]) -> Result<BipartiteGraph, ParseError> {
+    let mut parser = LineParser::new();
+    let mut position = 0;
+    let mut line_number = 0;
+
+    // Parse the header and the cutwidth ordering sequentially
+    while position < bytes.len() && !parser.expects_edges() {
+        let end = line_end(bytes, position);
+        line_number += 1;
+        parser.parse_line(&line_str(&bytes[position..end]), line_number)?;
+        position = (end + 1).min(bytes.len());
+    }
+
+    // Parse the edge chunks in parallel
+    let (top_node_count, bottom_node_count) = (
+        parser.graph().top_node_count,
+        parser.graph().bottom_node_count,
+    );
+    let chunks = split_chunks(bytes, position);
+    let results: Vec<Option<(Vec<Edge>, Vec<u64>)>> = chunks
+        .par_iter()
+        .map(|chunk| parse_edge_chunk(&bytes[chunk.clone()], top_node_count, bottom_node_count))
+        .collect();
+
+    for (chunk, edges) in chunks.into_iter().zip(results) {
+        match edges {
+            Some((edges, weights)) if edges.len() as u64 <= parser.edges_left() => {
+                parser.extend_edges(edges, weights)
+            }
+            _ => {
+                // Invalid line or too many edges: parse the chunk again line by line to report the exact error
+                let mut chunk_line_number = line_number
+                    + bytes[position..chunk.start]
+                        .iter()
+                        .filter(|&&b| b == b'\n')
+                        .count();
+                for line in bytes[chunk].split(|&byte| byte == b'\n') {
+                    chunk_line_number += 1;
+                    parser.parse_line(&line_str(line), chunk_line_number)?;
+                }
+            }
+        }
+    }
+
+    parser.finish()
+}
+
+/// Parse the edges of a chunk of lines, and their weights (empty if all weights are 1).
+/// Returns `None` if a line is not a valid edge, if a vertex index is out of range, or if a weight is out of range.
+fn parse_edge_chunk(
+    chunk: &[u8],
+    top_node_count: u64,
+    bottom_node_count: u64,
+) -> Option<(Vec<Edge>, Vec<u64>)> {
+    // Edge lines are at least 4 bytes long
+    let mut graph = BipartiteGraph::new();
+    graph.edges.reserve(chunk.len() / 4);
+    let node_count = top_node_count + bottom_node_count;
+
+    for line in chunk.split(|&byte| byte == b'\n') {
+        let line = line.trim_ascii();
+        if line.is_empty() || line[0] == b'c' {
+            continue; // Blank or comment line
+        }
+
+        let (top, rest) = parse_uint(line)?;
+        let (bottom, rest) = parse_uint(skip_separator(rest)?)?;
+        let (weight, rest) = if rest.is_empty() {
+            (1, rest)
+        } else {
+            parse_uint(skip_separator(rest)?)?
+        };
+
+        if !rest.is_empty()
+            || top < 1
+            || top > top_node_count
+            || bottom <= top_node_count
+            || bottom > node_count
+            || weight == 0
+            || weight > MAX_WEIGHT
+        {
+            return None;
+        }
+
+        graph.push_edge((top, bottom), weight);
+    }
+
+    Some((graph.edges, graph.weights))
+}
+
+/// Skip the spaces and tabs at the start of a byte slice. Returns `None` if there are none
+fn skip_separator(bytes: &[u8]) -> Option<&[u8]> {
+    let separator_length = bytes
+        .iter()
+        .position(|&byte| byte != b' ' && byte != b'\t')
+        .unwrap_or(bytes.len());
+    if separator_length == 0 {
+        return None;
+    }
+    Some(&bytes[separator_length..])
+}
+
+/// Parse a decimal unsigned integer at the start of a byte slice, and return the remaining bytes
+fn parse_uint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let digits = bytes
+        .iter()
+        .position(|byte| !byte.is_ascii_digit())
+        .unwrap_or(bytes.len());
+    if digits == 0 {
+        return None;
+    }
+
+    let mut value = 0_u64;
+    for &digit in &bytes[..digits] {
+        value = value.checked_mul(10)?.checked_add((digit - b'0') as u64)?;
+    }
+
+    Some((value, &bytes[digits..]))
+}
+
+/// Split `bytes[start..]` into chunks of whole lines, so that every thread gets several chunks
+fn split_chunks(bytes: &[u8], start: usize) -> Vec<Range<usize>> {
+    let chunk_size = (bytes.len().saturating_sub(start) / (rayon::current_num_threads() * 4))
+        .max(MIN_CHUNK_SIZE);
+
+    let mut chunks = Vec::new();
+    let mut chunk_start = start;
+    while chunk_start < bytes.len() {
+        let mut chunk_end = (chunk_start + chunk_size).min(bytes.len());
+        if chunk_end < bytes.len() {
+            // Extend the chunk to the end of its last line
+            chunk_end = (line_end(bytes, chunk_end) + 1).min(bytes.len());
+        }
+        chunks.push(chunk_start..chunk_end);
+        chunk_start = chunk_end;
+    }
+
+    chunks
+}
+
+/// Index of the end of the line starting at `start` (position of its `\n`, or the end of the buffer)
+fn line_end(bytes: &[u8], start: usize) -> usize {
+    bytes[start..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(bytes.len(), |offset| start + offset)
+}
+
+/// View a line as a string for the regular line parser
+fn line_str(line: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(line)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::File,
+        io::{BufWriter, Write},
+        time::Instant,
+    };
+
+    use walkdir::WalkDir;
+
+    use super::*;
+    use crate::{parse_file, parse_str};
+
+    #[test]
+    fn mmap_parser_matches_line_parser() {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .chain(WalkDir::new("../datasets/medium"))
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let filename = file.path().to_str().unwrap();
+            let expected = parse_file(filename).unwrap();
+            let graph = parse_file_mmap(filename).unwrap();
+
+            assert_eq!(graph.top_node_count, expected.top_node_count);
+            assert_eq!(graph.bottom_node_count, expected.bottom_node_count);
+            assert_eq!(graph.edges, expected.edges, "{}", filename);
+        }
+
+        // Errors must be reported identically
+        for content in [
+            "c comment\np ocr 2 2 2 1\n1\n2\n3\n4\n1 3\r\nc comment\n\t2  4 \n",
+            "p ocr 2 2 1\n1 x\n",
+            "p ocr 2 2 1\n  1 5\n",
+            "p ocr 2 2 2\n1 3\n",
+            "p ocr 2 2 1\n1 3\n2 4\n",
+            "p ocr 2 2 1\n13\n",
+            "p ocr 2 2 1 3\n1\n",
+            "p ocr 2 2 3\n1 3 2\n2\t4\n2 3  5 \n",
+            "p ocr 2 2 1\n1 3 0\n",
+            "p ocr 2 2 1\n1 3 2 1\n",
+            "p ocr 18446744073709551615 1 0\n",
+            "p ocr 1 1 99999999999999\n1 2\n",
+        ] {
+            let expected = parse_str(content).map(|graph| (graph.edges, graph.weights));
+            let parsed = parse_bytes(content.as_bytes()).map(|graph| (graph.edges, graph.weights));
+
+            assert_eq!(
+                format!("{:?}", parsed),
+                format!("{:?}", expected),
+                "{:?}",
+                content
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "parses all the large datasets, run with --ignored"]
+    fn benchmark_parsers() {
+        println!("Benchmarking parsers...");
+
+        let files = WalkDir::new("../datasets/large")
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file())
+            .filter_map(|e| e.path().to_str().map(|s| s.to_string()))
+            .collect::<Vec<_>>();
+
+        let file = File::create("../parser_benchmark.csv").unwrap();
+        let mut writer = BufWriter::new(file);
+        let mut total_line_parser = 0;
+        let mut total_mmap_parser = 0;
+
+        // Files are processed one at a time, because the memory-mapped parser already uses all threads
+        for filename in &files {
+            let start_time = Instant::now();
+            let expected = parse_file(filename).unwrap();
+            let line_parser_time = start_time.elapsed().as_nanos();
+
+            let start_time = Instant::now();
+            let graph = parse_file_mmap(filename).unwrap();
+            let mmap_parser_time = start_time.elapsed().as_nanos();
+
+            assert_eq!(graph.edges, expected.edges);
+
+            writeln!(
+                writer,
+                "{},{},{},{}",
+                graph.top_node_count + graph.bottom_node_count,
+                graph.edges.len(),
+                line_parser_time,
+                mmap_parser_time
+            )
+            .unwrap();
+
+            total_line_parser += line_parser_time;
+            total_mmap_parser += mmap_parser_time;
+        }
+
+        println!(
+            "parse_file: {} ms, parse_file_mmap: {} ms, speedup: {:.2}x",
+            total_line_parser / 1_000_000,
+            total_mmap_parser / 1_000_000,
+            total_line_parser as f64 / total_mmap_parser.max(1) as f64
+        );
+    }
+}
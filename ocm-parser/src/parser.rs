@@ -1,21 +1,258 @@
 //! Parsing utils for the graph files
+//!
+//! The grammar follows the PACE 2024 format:
+//! - Lines starting with `c` are comments, and may appear anywhere.
+//! - The header line is `p ocr <top> <bottom> <edges> [<cutwidth>]`.
+//! - If a cutwidth is given, the header is followed by `<top> + <bottom>` lines containing one vertex each.
+//! - Each edge line is `<top> <bottom> [<weight>]`. Edges without a weight have a weight of 1,
+//!   and weights must be in `[1, 65535]`.
+//!
+//! Tokens may be separated by any amount of spaces or tabs. The line parsers below expect lines
+//! without their line ending and leading whitespace.
 
-use winnow::ascii::dec_uint;
+use winnow::ascii::{dec_uint, space0, space1};
+use winnow::combinator::{opt, preceded, terminated};
 use winnow::PResult;
 use winnow::Parser;
 
-use crate::graph_base::Edge;
+use crate::{
+    bipartite_graph::BipartiteGraph,
+    error::{ParseError, ParseErrorKind},
+    graph_base::{Edge, WeightedEdge, MAX_WEIGHT},
+};
+
+/// Largest number of edges reserved from the edge count announced by the header
+const MAX_RESERVED_EDGES: u64 = 1 << 24;
+
+/// Content of the header line of a graph file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphHeader {
+    pub top_node_count: u64,
+    pub bottom_node_count: u64,
+    pub edge_count: u64,
+    /// Cutwidth of the vertex ordering that follows the header (parameterized track instances only)
+    pub cutwidth: Option<u64>,
+}
+
+/// Whether a line is a comment line
+pub fn is_comment(line: &str) -> bool {
+    line.starts_with('c')
+}
 
 /// Parse the header of a graph file
-pub fn parse_graph_header(input: &mut &str) -> PResult<(u64, u64, u64)> {
-    let (_, top_vertices_count, _, bottom_vertices_count, _, edge_count) =
-        ("p ocr ", dec_uint, ' ', dec_uint, ' ', dec_uint).parse_next(input)?;
-    Ok((top_vertices_count, bottom_vertices_count, edge_count))
+pub fn parse_graph_header(input: &mut &str) -> PResult<GraphHeader> {
+    let (_, _, _, top_node_count, _, bottom_node_count, _, edge_count, cutwidth, _) = (
+        'p',
+        preceded(space1, "ocr"),
+        space1,
+        dec_uint,
+        space1,
+        dec_uint,
+        space1,
+        dec_uint,
+        opt(preceded(space1, dec_uint)),
+        space0,
+    )
+        .parse_next(input)?;
+
+    Ok(GraphHeader {
+        top_node_count,
+        bottom_node_count,
+        edge_count,
+        cutwidth,
+    })
+}
+
+/// Parse a vertex from a line of the cutwidth ordering
+pub fn parse_ordering_vertex(input: &mut &str) -> PResult<u64> {
+    terminated(dec_uint, space0).parse_next(input)
+}
+
+/// Parse an edge from a line of the graph file, with a weight of 1 if it has none
+pub fn parse_graph_edges(input: &mut &str) -> PResult<WeightedEdge> {
+    let (top_vertex_index, _, bottom_vertex_index, weight, _) = (
+        dec_uint,
+        space1,
+        dec_uint,
+        opt(preceded(space1, dec_uint)),
+        space0,
+    )
+        .parse_next(input)?;
+
+    Ok((top_vertex_index, bottom_vertex_index, weight.unwrap_or(1)))
+}
+
+/// Incremental graph file parser, fed one line at a time.
+/// Blank and comment lines are skipped, and vertex indices are validated against the header counts.
+pub(crate) struct LineParser {
+    graph: BipartiteGraph,
+    header: Option<GraphHeader>,
+    ordering_lines_left: u64,
+}
+
+impl LineParser {
+    pub fn new() -> Self {
+        LineParser {
+            graph: BipartiteGraph::new(),
+            header: None,
+            ordering_lines_left: 0,
+        }
+    }
+
+    /// Whether the header and the cutwidth ordering have been read, i.e. all following lines are edges
+    pub fn expects_edges(&self) -> bool {
+        self.header.is_some() && self.ordering_lines_left == 0
+    }
+
+    /// Number of edges that can still be read before reaching the count announced by the header
+    pub fn edges_left(&self) -> u64 {
+        self.header.map_or(0, |header| {
+            header.edge_count - self.graph.edges.len() as u64
+        })
+    }
+
+    /// Graph being parsed
+    pub fn graph(&self) -> &BipartiteGraph {
+        &self.graph
+    }
+
+    /// Append edges that were parsed and validated elsewhere, with their weights (empty if all weights are 1)
+    pub fn extend_edges(&mut self, edges: Vec<Edge>, weights: Vec<u64>) {
+        self.graph.extend_edges(edges, weights);
+    }
+
+    /// Parse a raw line (without its line ending), whose 1-based number is given for error reporting
+    pub fn parse_line(&mut self, line: &str, line_number: usize) -> Result<(), ParseError> {
+        // Skip blank and comment lines, and keep track of the leading whitespace for error columns
+        let content = line.trim();
+        if content.is_empty() || is_comment(content) {
+            return Ok(());
+        }
+        let offset = line.len() - line.trim_start().len();
+        let error = |kind, column| ParseError::new(kind, line_number, offset + column, content);
+
+        let Some(header) = &self.header else {
+            // The first meaningful line is the header
+            let parsed = parse_line(parse_graph_header, content)
+                .map_err(|column| error(ParseErrorKind::InvalidHeader, column))?;
+            let node_count = parsed
+                .top_node_count
+                .checked_add(parsed.bottom_node_count)
+                .ok_or_else(|| error(ParseErrorKind::InvalidHeader, 1))?;
+            self.graph.top_node_count = parsed.top_node_count;
+            self.graph.bottom_node_count = parsed.bottom_node_count;
+            // The edge count is not trusted yet, the edges vector grows as needed beyond this reservation
+            self.graph
+                .edges
+                .reserve(parsed.edge_count.min(MAX_RESERVED_EDGES) as usize);
+            if parsed.cutwidth.is_some() {
+                self.ordering_lines_left = node_count;
+            }
+            self.header = Some(parsed);
+            return Ok(());
+        };
+
+        if self.ordering_lines_left > 0 {
+            // Cutwidth ordering lines. The ordering is validated, but not kept.
+            let node_count = self.graph.top_node_count + self.graph.bottom_node_count;
+            let vertex = parse_line(parse_ordering_vertex, content)
+                .map_err(|column| error(ParseErrorKind::InvalidOrdering, column))?;
+            check_range(vertex, 1, node_count).map_err(|kind| error(kind, 1))?;
+            self.ordering_lines_left -= 1;
+        } else if (self.graph.edges.len() as u64) < header.edge_count {
+            let (top, bottom, weight) = parse_edge_line(
+                content,
+                self.graph.top_node_count,
+                self.graph.bottom_node_count,
+            )
+            .map_err(|(kind, column)| error(kind, column))?;
+            self.graph.push_edge((top, bottom), weight);
+        } else {
+            return Err(error(ParseErrorKind::UnexpectedLine, 1));
+        }
+
+        Ok(())
+    }
+
+    /// Check that the whole file was read, and return the parsed graph
+    pub fn finish(self) -> Result<BipartiteGraph, ParseError> {
+        let Some(header) = self.header else {
+            return Err(ParseError::new(ParseErrorKind::MissingHeader, 0, 0, ""));
+        };
+
+        if self.ordering_lines_left > 0 {
+            let node_count = header.top_node_count + header.bottom_node_count;
+            return Err(ParseError::new(
+                ParseErrorKind::MissingOrdering {
+                    expected: node_count,
+                    found: node_count - self.ordering_lines_left,
+                },
+                0,
+                0,
+                "",
+            ));
+        }
+
+        if (self.graph.edges.len() as u64) < header.edge_count {
+            return Err(ParseError::new(
+                ParseErrorKind::MissingEdges {
+                    expected: header.edge_count,
+                    found: self.graph.edges.len() as u64,
+                },
+                0,
+                0,
+                "",
+            ));
+        }
+
+        Ok(self.graph)
+    }
+}
+
+/// Parse and validate a trimmed edge line.
+/// On failure, returns the error kind and the 1-based column of the offending token.
+fn parse_edge_line(
+    content: &str,
+    top_node_count: u64,
+    bottom_node_count: u64,
+) -> Result<WeightedEdge, (ParseErrorKind, usize)> {
+    let (top, bottom, weight) = parse_line(parse_graph_edges, content)
+        .map_err(|column| (ParseErrorKind::InvalidEdge, column))?;
+
+    check_range(top, 1, top_node_count).map_err(|kind| (kind, 1))?;
+    check_range(
+        bottom,
+        top_node_count + 1,
+        top_node_count + bottom_node_count,
+    )
+    .map_err(|kind| (kind, token_column(content, 1)))?;
+    if weight == 0 || weight > MAX_WEIGHT {
+        return Err((ParseErrorKind::InvalidWeight, token_column(content, 2)));
+    }
+
+    Ok((top, bottom, weight))
+}
+
+/// Run a line parser over a whole line.
+/// On failure, returns the 1-based column at which the parser stopped.
+fn parse_line<O>(mut parser: fn(&mut &str) -> PResult<O>, content: &str) -> Result<O, usize> {
+    parser.parse(content).map_err(|error| error.offset() + 1)
 }
 
-/// Parse an edge from a line of the graph file
-pub fn parse_graph_edges(input: &mut &str) -> PResult<Edge> {
-    let (top_vertex_index, _, bottom_vertex_index) = (dec_uint, ' ', dec_uint).parse_next(input)?;
+/// Check that a vertex index lies within `[min, max]`
+fn check_range(vertex: u64, min: u64, max: u64) -> Result<(), ParseErrorKind> {
+    if vertex < min || vertex > max {
+        return Err(ParseErrorKind::VertexOutOfRange { vertex, min, max });
+    }
+    Ok(())
+}
 
-    Ok((top_vertex_index, bottom_vertex_index))
+/// 1-based column of the whitespace separated token of a trimmed line at the given 0-based index
+fn token_column(content: &str, token: usize) -> usize {
+    let mut rest = content;
+    for _ in 0..token {
+        let token_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        rest = rest[token_end..].trim_start();
+    }
+    content.len() - rest.len() + 1
 }
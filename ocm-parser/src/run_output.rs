@@ -11,10 +11,13 @@ pub struct RunOutput {
     initial_crossings: u64,
     final_crossings: u64,
     elapsed_nanos: u64,
+    lower_bound: u64,
+    relative_gap: f64,
 }
 
 impl RunOutput {
-    /// Create a new RunOutput
+    /// Create a new RunOutput. The values are given in the same order as in the file
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         filename: &str,
         algorithm: &str,
@@ -22,6 +25,8 @@ impl RunOutput {
         initial_crossings: u64,
         final_crossings: u64,
         elapsed_nanos: u64,
+        lower_bound: u64,
+        relative_gap: f64,
     ) -> Self {
         RunOutput {
             filename: filename.to_string(),
@@ -30,6 +35,8 @@ impl RunOutput {
             initial_crossings,
             final_crossings,
             elapsed_nanos,
+            lower_bound,
+            relative_gap,
         }
     }
 
@@ -38,6 +45,8 @@ impl RunOutput {
     /// <initial_crossings>
     /// <final_crossings>
     /// <elapsed_nanos>
+    /// <lower_bound>
+    /// <relative_gap> (between the final crossings and the lower bound, in [0, 1])
     pub fn save_to_file(&self) {
         let out_path = format!("analytics/{}/{}", self.algorithm, self.dataset);
 
@@ -48,7 +57,7 @@ impl RunOutput {
         let mut file = std::fs::File::create(format!(
             "{}/{}.txt",
             out_path,
-            self.filename.split('/').last().unwrap()
+            self.filename.split('/').next_back().unwrap()
         ))
         .unwrap();
 
@@ -58,5 +67,9 @@ impl RunOutput {
             .unwrap();
         file.write_all(format!("{}\n", self.elapsed_nanos).as_bytes())
             .unwrap();
+        file.write_all(format!("{}\n", self.lower_bound).as_bytes())
+            .unwrap();
+        file.write_all(format!("{}\n", self.relative_gap).as_bytes())
+            .unwrap();
     }
 }
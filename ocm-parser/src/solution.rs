@@ -0,0 +1,131 @@
+//! PACE 2024 solution files.
+//!
+//! A solution lists the bottom layer vertex IDs (numbered from `top_node_count + 1`), one per line,
+//! in their new left-to-right order. Lines starting with `c` are comments.
+
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    bipartite_graph::BipartiteGraph,
+    compression::{create_file, open_file},
+};
+
+/// Write a solution to any writer
+pub fn write_solution<W: Write>(writer: &mut W, bottom_order: &[u64]) -> io::Result<()> {
+    for id in bottom_order {
+        writeln!(writer, "{}", id)?;
+    }
+    writer.flush()
+}
+
+/// Save a solution to a file. The file is compressed if its extension is `.gz`, `.zst` or `.xz`.
+pub fn save_solution_to_file(filename: &str, bottom_order: &[u64]) -> io::Result<()> {
+    let mut writer = create_file(filename)?;
+    write_solution(&mut writer, bottom_order)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Parse a solution from a buffered reader. Comment and blank lines are skipped.
+pub fn parse_solution<R: BufRead>(reader: R) -> io::Result<Vec<u64>> {
+    let mut bottom_order = Vec::new();
+
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('c') {
+            continue;
+        }
+
+        let id = line.parse::<u64>().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: invalid vertex ID \"{}\"", index + 1, line),
+            )
+        })?;
+        bottom_order.push(id);
+    }
+
+    Ok(bottom_order)
+}
+
+/// Parse a solution from a file, transparently decompressing it if needed
+pub fn parse_solution_file(filename: &str) -> io::Result<Vec<u64>> {
+    parse_solution(open_file(filename)?)
+}
+
+/// Check that a solution is a permutation of the bottom vertex IDs of a graph
+pub fn validate_solution(graph: &BipartiteGraph, bottom_order: &[u64]) -> io::Result<()> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+
+    if bottom_order.len() as u64 != graph.bottom_node_count {
+        return Err(invalid(format!(
+            "expected {} bottom vertices, found {}",
+            graph.bottom_node_count,
+            bottom_order.len()
+        )));
+    }
+
+    let mut seen = vec![false; graph.bottom_node_count as usize];
+    for &id in bottom_order {
+        if id <= graph.top_node_count || id > graph.top_node_count + graph.bottom_node_count {
+            return Err(invalid(format!("{} is not a bottom vertex ID", id)));
+        }
+
+        let index = (id - graph.top_node_count - 1) as usize;
+        if seen[index] {
+            return Err(invalid(format!("bottom vertex {} appears twice", id)));
+        }
+        seen[index] = true;
+    }
+
+    Ok(())
+}
+
+/// Convert 0-based bottom node indices into PACE vertex IDs
+pub fn indices_to_ids(top_node_count: u64, bottom_order: &[usize]) -> Vec<u64> {
+    bottom_order
+        .iter()
+        .map(|&index| index as u64 + top_node_count + 1)
+        .collect()
+}
+
+/// Convert PACE vertex IDs into 0-based bottom node indices
+pub fn ids_to_indices(top_node_count: u64, bottom_order: &[u64]) -> Vec<usize> {
+    bottom_order
+        .iter()
+        .map(|&id| (id - top_node_count - 1) as usize)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solution_round_trip() {
+        let graph = BipartiteGraph {
+            top_node_count: 2,
+            bottom_node_count: 3,
+            edges: vec![(1, 3), (2, 4), (2, 5)],
+            ..Default::default()
+        };
+        let bottom_order = vec![5, 3, 4];
+
+        let mut buffer = Vec::new();
+        write_solution(&mut buffer, &bottom_order).unwrap();
+        assert_eq!(buffer, b"5\n3\n4\n");
+
+        let parsed = parse_solution(&b"c comment\n5\n3\n\n4\n"[..]).unwrap();
+        assert_eq!(parsed, bottom_order);
+        assert!(validate_solution(&graph, &parsed).is_ok());
+
+        assert!(validate_solution(&graph, &[5, 3]).is_err());
+        assert!(validate_solution(&graph, &[5, 3, 3]).is_err());
+        assert!(validate_solution(&graph, &[5, 3, 2]).is_err());
+
+        assert_eq!(ids_to_indices(2, &bottom_order), vec![2, 0, 1]);
+        assert_eq!(indices_to_ids(2, &[2, 0, 1]), bottom_order);
+    }
+}
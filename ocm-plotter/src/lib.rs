@@ -7,9 +7,9 @@ use plotters::{backend::DrawingBackend, coord::Shift, drawing::DrawingArea};
 pub mod plottable;
 
 /// Example implementation for Bipartite Graph.
-impl<'a, DB> Plottable<DB> for BipartiteGraph
+impl<DB> Plottable<DB> for BipartiteGraph
 where
-    DB: DrawingBackend + 'a,
+    DB: DrawingBackend,
 {
     fn plot(&self, root: &mut DrawingArea<DB, Shift>) {
         root.fill(&WHITE).unwrap();
@@ -83,9 +83,9 @@ where
 
 /// Example implementation for Abscissa Graph.
 /// As the positions are already scaled within [-1, 1], plotting is simpler.
-impl<'a, DB> Plottable<DB> for AbscissaGraph
+impl<DB> Plottable<DB> for AbscissaGraph
 where
-    DB: DrawingBackend + 'a,
+    DB: DrawingBackend,
 {
     fn plot(&self, root: &mut DrawingArea<DB, Shift>) {
         root.fill(&WHITE).unwrap();
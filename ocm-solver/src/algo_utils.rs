@@ -1,28 +1,33 @@
 //! Some utility functions for algorithms
 
-use std::ops::Add;
+use ocm_parser::graph_base::WeightedEdge;
 
-use ocm_parser::graph_base::Edge;
-
-/// Given a sorted array, returns its median if it exists.
+/// Given a sorted array of abscissas, returns the median interpolated towards the denser side if it exists
+/// (Gansner et al., "A technique for drawing directed graphs").
 ///
 /// Returns
 /// -------
 /// - `None` if the array is empty
 /// - `Some(median)` if the array has an odd number of elements
-/// - `Some((mid1 + mid2) / 2)` if the array has an even number of elements
-pub fn sorted_array_median<T: Add<Output = T> + std::ops::Div<Output = T> + From<i32> + Copy>(
-    array: &[T],
-) -> Option<T> {
+/// - `Some((mid1 + mid2) / 2)` if the array has 2 elements, or if both halves have no spread
+/// - Otherwise, the average of the two middle values weighted by the spread of the opposite half,
+///   `(mid1 * right + mid2 * left) / (left + right)`, where `left` and `right` are the spreads of the halves
+pub fn sorted_array_interpolated_median(array: &[f64]) -> Option<f64> {
     let len = array.len();
 
     match len {
         0 => None,
         _ if len % 2 == 1 => Some(array[len / 2]),
+        2 => Some((array[0] + array[1]) / 2.0),
         _ => {
-            let mid1 = array[len / 2 - 1];
-            let mid2 = array[len / 2];
-            Some((mid1 + mid2) / T::from(2))
+            let (mid1, mid2) = (array[len / 2 - 1], array[len / 2]);
+            let left = mid1 - array[0];
+            let right = array[len - 1] - mid2;
+            if left + right == 0.0 {
+                Some((mid1 + mid2) / 2.0)
+            } else {
+                Some((mid1 * right + mid2 * left) / (left + right))
+            }
         }
     }
 }
@@ -47,8 +52,25 @@ pub fn rank_index_array<T: PartialOrd>(array: &[T]) -> Vec<usize> {
     ranks
 }
 
+/// Same as [`rank_index_array`], but equal values are ranked by increasing tie key instead of by index
+pub fn rank_index_array_by<T: PartialOrd, K: Ord>(array: &[T], tie_keys: &[K]) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..array.len()).collect();
+    indices.sort_by(|&i, &j| {
+        array[i]
+            .partial_cmp(&array[j])
+            .unwrap()
+            .then_with(|| tie_keys[i].cmp(&tie_keys[j]))
+    });
+
+    let mut ranks = vec![0; array.len()];
+    for (rank, &index) in indices.iter().enumerate() {
+        ranks[index] = rank;
+    }
+    ranks
+}
+
 /// Sort a slice of edges in place in lexicographic order, i.e. first by the first node index, then by the second node index.
-pub fn edges_lexicographic_sort(edges: &mut [Edge]) {
+pub fn edges_lexicographic_sort(edges: &mut [WeightedEdge]) {
     edges.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.cmp(&b.1)));
 }
 
@@ -56,6 +78,42 @@ pub fn edges_lexicographic_sort(edges: &mut [Edge]) {
 ///
 /// This sort is useful when dealing with a line sweep algorithm that needs to progressively add
 /// active edges when iterating with a vertical line that goes through the top and bottom u64-indexed nodes
-pub fn edges_min_index_sort(edges: &mut [Edge]) {
-    edges.sort_by(|a, b| a.0.min(a.1).cmp(&b.0.min(b.1)));
+pub fn edges_min_index_sort(edges: &mut [WeightedEdge]) {
+    edges.sort_by_key(|edge| edge.0.min(edge.1));
+}
+
+/// Accumulator tree (a.k.a. Fenwick tree or binary indexed tree), storing values at indices `0..size`
+/// and computing prefix sums in logarithmic time.
+#[derive(Debug, Clone)]
+pub struct FenwickTree {
+    tree: Vec<u64>,
+}
+
+impl FenwickTree {
+    /// Create a tree with all values set to 0
+    pub fn new(size: usize) -> Self {
+        FenwickTree {
+            tree: vec![0; size + 1],
+        }
+    }
+
+    /// Add `value` at `index`. Time: `O(log(size))`
+    pub fn add(&mut self, index: usize, value: u64) {
+        let mut i = index + 1;
+        while i < self.tree.len() {
+            self.tree[i] += value;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// Sum of the values at indices `0..=index`. Time: `O(log(size))`
+    pub fn prefix_sum(&self, index: usize) -> u64 {
+        let mut sum = 0;
+        let mut i = (index + 1).min(self.tree.len() - 1);
+        while i > 0 {
+            sum += self.tree[i];
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
 }
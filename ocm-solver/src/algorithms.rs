@@ -1,56 +1,473 @@
-use std::fmt::{Display, Formatter};
+use std::{
+    fmt::{Display, Formatter},
+    sync::Arc,
+    time::Duration,
+};
 
 use ocm_parser::bipartite_graph::BipartiteGraph;
+use rand::{seq::SliceRandom, Rng};
 
-use crate::graphs::abscissa_graph::AbscissaGraph;
+use crate::{
+    algo_utils::{rank_index_array, rank_index_array_by},
+    cancellation::CancellationToken,
+    crossings::count_crossings,
+    decomposition::solve_decomposed,
+    events::{EventObserver, SolverEvent},
+    graphs::abscissa_graph::AbscissaGraph,
+    precedence::Precedence,
+    reduction::solve_reduced,
+    solver::{Solver, SolverStats},
+};
 
+pub mod adjacent_exchange;
 pub mod barycenter_heuristic;
+pub mod branch_and_bound;
+pub mod exact_dp;
+pub mod feedback_arc_set;
 pub mod median_heuristic;
+pub mod memetic;
+pub mod portfolio;
+pub mod sifting;
+pub mod simulated_annealing;
+pub mod tabu_search;
 
-/// Algorithm to use for the generic solver
-#[derive(Debug, Clone, clap::ValueEnum)]
+/// Built-in algorithms, as a command line convenience. Each one is also a [`Solver`] with its default configuration,
+/// see [`Algorithm::solver`] to get it, and the configuration structs of the algorithm modules to change it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
 pub enum Algorithm {
     Median,
     IterMedian,
     Barycenter,
     IterBarycenter,
+    /// Exact solver, within the node and time limits
+    BranchAndBound,
+    /// Exact solver for small graphs, falls back to the median heuristic above the size limit
+    ExactDp,
+    /// Eades-Lin-Smyth heuristic on the penalty graph of the free nodes, see [`crate::penalty_graph`]
+    FeedbackArcSet,
+    /// Local search moving each node to its best position
+    Sifting,
+    /// Anytime metaheuristic, see [`SolveOptions::time_limit`]
+    SimulatedAnnealing,
+    /// Local search applying the best insertion move of a node that was not recently moved
+    TabuSearch,
+    /// Genetic algorithm improving its offspring with a local search, see [`SolveOptions::time_limit`]
+    Memetic,
+    /// Best solution of several algorithms run in parallel (the median and barycenter heuristics by default)
+    Portfolio,
+}
+
+impl Algorithm {
+    /// Solver of the algorithm, with its default configuration
+    pub fn solver(&self) -> Arc<dyn Solver> {
+        match self {
+            Algorithm::Median => Arc::new(median_heuristic::MedianConfig::default()),
+            Algorithm::IterMedian => Arc::new(median_heuristic::MedianConfig {
+                iterated: true,
+                ..Default::default()
+            }),
+            Algorithm::Barycenter => Arc::new(barycenter_heuristic::BarycenterConfig::default()),
+            Algorithm::IterBarycenter => Arc::new(barycenter_heuristic::BarycenterConfig {
+                iterated: true,
+                ..Default::default()
+            }),
+            Algorithm::BranchAndBound => {
+                Arc::new(branch_and_bound::BranchAndBoundConfig::default())
+            }
+            Algorithm::ExactDp => Arc::new(exact_dp::ExactDpConfig::default()),
+            Algorithm::FeedbackArcSet => {
+                Arc::new(feedback_arc_set::FeedbackArcSetConfig::default())
+            }
+            Algorithm::Sifting => Arc::new(sifting::SiftingConfig::default()),
+            Algorithm::SimulatedAnnealing => {
+                Arc::new(simulated_annealing::SimulatedAnnealingConfig::default())
+            }
+            Algorithm::TabuSearch => Arc::new(tabu_search::TabuSearchConfig::default()),
+            Algorithm::Memetic => Arc::new(memetic::MemeticConfig::default()),
+            Algorithm::Portfolio => Arc::new(portfolio::PortfolioConfig::default()),
+        }
+    }
+}
+
+impl Solver for Algorithm {
+    fn name(&self) -> &str {
+        match self {
+            Algorithm::Median => "median",
+            Algorithm::IterMedian => "iterated_median",
+            Algorithm::Barycenter => "barycenter",
+            Algorithm::IterBarycenter => "iterated_barycenter",
+            Algorithm::BranchAndBound => "branch_and_bound",
+            Algorithm::ExactDp => "exact_dp",
+            Algorithm::FeedbackArcSet => "feedback_arc_set",
+            Algorithm::Sifting => "sifting",
+            Algorithm::SimulatedAnnealing => "simulated_annealing",
+            Algorithm::TabuSearch => "tabu_search",
+            Algorithm::Memetic => "memetic",
+            Algorithm::Portfolio => "portfolio",
+        }
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        self.solver().parameters()
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        self.solver().solve(graph, options)
+    }
+}
+
+/// Layers whose node order may be changed by the solver.
+///
+/// The one-sided crossing minimization problem keeps the top layer fixed and only permutes the bottom layer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum FreeLayer {
+    /// Only the top layer is permuted, the bottom layer is fixed
+    Top,
+    /// Only the bottom layer is permuted, the top layer is fixed (one-sided crossing minimization)
+    #[default]
+    Bottom,
+    /// Both layers are permuted simultaneously
+    Both,
+}
+
+impl FreeLayer {
+    /// Whether the top layer nodes may be moved
+    pub fn top_is_free(&self) -> bool {
+        matches!(self, FreeLayer::Top | FreeLayer::Both)
+    }
+
+    /// Whether the bottom layer nodes may be moved
+    pub fn bottom_is_free(&self) -> bool {
+        matches!(self, FreeLayer::Bottom | FreeLayer::Both)
+    }
+}
+
+/// Post-optimization of the solution of an algorithm, see [`adjacent_exchange`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Refinement {
+    /// Passes over the order, swapping adjacent nodes whenever it removes crossings
+    AdjacentExchange,
+    /// Repeatedly apply the adjacent swap that removes the most crossings
+    GreedySwitch,
+}
+
+impl Display for Refinement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Refinement::AdjacentExchange => write!(f, "adjacent_exchange"),
+            Refinement::GreedySwitch => write!(f, "greedy_switch"),
+        }
+    }
+}
+
+/// Order of the nodes whose new positions are equal in the median and barycenter heuristics
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum TieBreak {
+    /// Keep the previous order of the nodes
+    #[default]
+    KeepPrevious,
+    /// Nodes with fewer neighbors first, then the previous order
+    Degree,
+    /// Random order, reproducible with a seed
+    Random,
+}
+
+impl Display for TieBreak {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TieBreak::KeepPrevious => write!(f, "keep_previous"),
+            TieBreak::Degree => write!(f, "degree"),
+            TieBreak::Random => write!(f, "random"),
+        }
+    }
+}
+
+/// Whether a solution is proven to be optimal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProofStatus {
+    /// No other order of the free layer(s) has fewer crossings
+    Optimal,
+    /// Valid solution, without optimality guarantee (heuristic, or exhausted search budget)
+    Feasible,
+}
+
+impl Display for ProofStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProofStatus::Optimal => write!(f, "optimal"),
+            ProofStatus::Feasible => write!(f, "feasible"),
+        }
+    }
+}
+
+/// Options shared by all solvers. The parameters of each algorithm are in its configuration struct
+#[derive(Debug, Clone, Default)]
+pub struct SolveOptions {
+    /// Layer(s) whose node order may be changed
+    pub free_layer: FreeLayer,
+    /// Maximum time spent by the exact and local search solvers
+    pub time_limit: Option<Duration>,
+    /// Token checked by the iterative algorithms, which stop with their best solution when it is cancelled
+    pub cancellation: CancellationToken,
+    /// Receiver of the progress events and diagnostics of the solvers
+    pub observer: EventObserver,
+    /// Reduce the instance before solving, see [`crate::reduction`] (ignored when both layers are free)
+    pub reduce: bool,
+    /// Forced relative orders of the bottom nodes, respected by the built-in solvers when only the bottom layer is free.
+    /// The reduction sets them for the solvers of its blocks, see [`crate::precedence`]
+    pub precedence: Option<Arc<Precedence>>,
+    /// Solve the independent components in parallel, see [`crate::decomposition`] (ignored when both layers are free)
+    pub decompose: bool,
+    /// Post-optimization applied after the solver, which never increases the crossing count
+    pub refinement: Option<Refinement>,
+}
+
+impl SolveOptions {
+    /// Precedence constraints of the bottom layer, if it is the only free one
+    pub fn bottom_precedence(&self) -> Option<&Precedence> {
+        self.precedence
+            .as_deref()
+            .filter(|_| self.free_layer == FreeLayer::Bottom)
+    }
 }
 
 /// Generic solve function for the generic bipartite graph input
 /// Returns the solution bipartite graph.
-pub fn solve(graph: &BipartiteGraph, algorithm: &Algorithm, verbose: bool) -> BipartiteGraph {
-    match algorithm {
-        Algorithm::Median => {
-            let mut graph: AbscissaGraph = graph.into();
-            median_heuristic::median_heuristic_solve(&mut graph);
-            (&graph).into()
-        }
-        Algorithm::IterMedian => {
-            let mut graph = graph.into();
-            median_heuristic::iterated_median_heuristic_solve(&mut graph, verbose);
-            (&graph).into()
+pub fn solve(
+    graph: &BipartiteGraph,
+    solver: &dyn Solver,
+    options: &SolveOptions,
+) -> BipartiteGraph {
+    let mut graph: AbscissaGraph = graph.into();
+    solve_in_place(&mut graph, solver, options);
+    (&graph).into()
+}
+
+/// Generic in-place solve function, starting from the current node order of the graph.
+/// The solver runs after the optional reduction or decomposition, and before the optional refinement.
+/// The solution node order can be read back from the abscissas, see [`AbscissaGraph::bottom_order`].
+/// Returns the statistics of the solver, summed over the blocks or components.
+pub fn solve_in_place(
+    graph: &mut AbscissaGraph,
+    solver: &dyn Solver,
+    options: &SolveOptions,
+) -> SolverStats {
+    let SolveOptions {
+        free_layer, reduce, ..
+    } = *options;
+
+    if options.decompose && free_layer != FreeLayer::Both {
+        let mut iterations = 0;
+        let status = solve_free_layers(graph, free_layer, |layer_graph| {
+            let (status, stats) = solve_decomposed(layer_graph, solver, options);
+            iterations += stats
+                .iter()
+                .map(|component| component.iterations)
+                .sum::<u64>();
+            status
+        });
+        return SolverStats { status, iterations };
+    }
+
+    if reduce && free_layer != FreeLayer::Both {
+        // Solve the blocks of the reduced instance, with the bottom layer free
+        let block_options = SolveOptions {
+            free_layer: FreeLayer::Bottom,
+            reduce: false,
+            ..options.clone()
+        };
+        let mut iterations = 0;
+        let status = solve_free_layers(graph, free_layer, |layer_graph| {
+            solve_reduced(layer_graph, &options.observer, |block_graph, precedence| {
+                let block_options = SolveOptions {
+                    precedence: Some(Arc::new(precedence)),
+                    ..block_options.clone()
+                };
+                let stats = solve_in_place(block_graph, solver, &block_options);
+                iterations += stats.iterations;
+                stats.status
+            })
+        });
+        return SolverStats { status, iterations };
+    }
+
+    let stats = solver.solve(graph, options);
+
+    // Skip the refinement when cancelled, to return the solution as soon as possible
+    if let Some(refinement) = options
+        .refinement
+        .filter(|_| !options.cancellation.is_cancelled())
+    {
+        let removed = adjacent_exchange::refine(graph, refinement, free_layer);
+        options.observer.notify(SolverEvent::Refined {
+            refinement,
+            removed,
+        });
+    }
+
+    stats
+}
+
+/// Best bottom order among the current one and the median and barycenter heuristic ones,
+/// which respect the precedence constraints if any
+pub(crate) fn best_heuristic_order(
+    graph: &AbscissaGraph,
+    precedence: Option<&Precedence>,
+) -> Vec<usize> {
+    let mut median_graph = graph.clone();
+    median_heuristic::median_heuristic_solve(&mut median_graph, FreeLayer::Bottom);
+    constrain_bottom_order(&mut median_graph, precedence);
+    let mut barycenter_graph = graph.clone();
+    barycenter_heuristic::barycenter_heuristic_solve(&mut barycenter_graph, FreeLayer::Bottom);
+    constrain_bottom_order(&mut barycenter_graph, precedence);
+    let mut current_graph = graph.clone();
+    constrain_bottom_order(&mut current_graph, precedence);
+
+    [&current_graph, &median_graph, &barycenter_graph]
+        .into_iter()
+        .min_by_key(|candidate| count_crossings(*candidate))
+        .unwrap()
+        .bottom_order()
+}
+
+/// Reorder the bottom layer of a graph to respect the precedence constraints if any,
+/// see [`Precedence::linear_extension`]
+pub(crate) fn constrain_bottom_order(graph: &mut AbscissaGraph, precedence: Option<&Precedence>) {
+    if let Some(precedence) = precedence {
+        let order = graph.bottom_order();
+        if !precedence.respects(&order) {
+            graph.set_bottom_order(&precedence.linear_extension(&order));
         }
-        Algorithm::Barycenter => {
-            let mut graph = graph.into();
-            barycenter_heuristic::barycenter_heuristic_solve(&mut graph);
-            (&graph).into()
+    }
+}
+
+/// Replace the new positions of the nodes of a layer by their ranks, breaking the ties with a policy
+/// so that the order of the layer does not depend on the node indices.
+/// `previous` are the positions before the heuristic step, and `degrees` the number of neighbors of each node
+pub(crate) fn break_ties<R: Rng>(
+    positions: &mut [f64],
+    previous: &[f64],
+    degrees: &[usize],
+    tie_break: TieBreak,
+    rng: &mut R,
+) {
+    let previous_ranks = rank_index_array(previous);
+    let ranks = match tie_break {
+        TieBreak::KeepPrevious => rank_index_array_by(positions, &previous_ranks),
+        TieBreak::Degree => {
+            let keys: Vec<(usize, usize)> = degrees.iter().copied().zip(previous_ranks).collect();
+            rank_index_array_by(positions, &keys)
         }
-        Algorithm::IterBarycenter => {
-            let mut graph = graph.into();
-            barycenter_heuristic::iterated_barycenter_heuristic_solve(&mut graph, verbose);
-            (&graph).into()
+        TieBreak::Random => {
+            let mut keys: Vec<usize> = (0..positions.len()).collect();
+            keys.shuffle(rng);
+            rank_index_array_by(positions, &keys)
         }
+    };
+
+    positions
+        .iter_mut()
+        .zip(ranks)
+        .for_each(|(position, rank)| *position = rank as f64);
+}
+
+/// Name of the solver building the starting order of a local search, for its parameters
+pub(crate) fn local_search_start_name(start: &Option<Arc<dyn Solver>>) -> String {
+    start.as_ref().map_or_else(
+        || "current_order".to_string(),
+        |start| start.name().to_string(),
+    )
+}
+
+/// Solve each free layer with the other one fixed, using a bottom layer solver.
+/// The top layer is solved on the transposed graph. When both layers are free, the bottom layer is solved first,
+/// and the solution is not proven optimal for the two-sided problem.
+pub(crate) fn solve_free_layers<F>(
+    graph: &mut AbscissaGraph,
+    free_layer: FreeLayer,
+    mut solve_bottom_layer: F,
+) -> ProofStatus
+where
+    F: FnMut(&mut AbscissaGraph) -> ProofStatus,
+{
+    let mut status = ProofStatus::Optimal;
+
+    if free_layer.bottom_is_free() {
+        status = solve_bottom_layer(graph);
+    }
+
+    if free_layer.top_is_free() {
+        let mut transposed = graph.transposed();
+        let top_status = solve_bottom_layer(&mut transposed);
+        graph.top_nodes_abscissas = transposed.bottom_nodes_abscissas;
+
+        status = match free_layer {
+            FreeLayer::Top => top_status,
+            _ => ProofStatus::Feasible,
+        };
     }
+
+    status
 }
 
 /// Directory names for the analytics output for each algorithm
 impl Display for Algorithm {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Algorithm::Median => write!(f, "median"),
-            Algorithm::IterMedian => write!(f, "iterated_median"),
-            Algorithm::Barycenter => write!(f, "barycenter"),
-            Algorithm::IterBarycenter => write!(f, "iterated_barycenter"),
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::ValueEnum;
+    use ocm_parser::parse_file;
+
+    use super::*;
+
+    #[test]
+    fn one_sided_solve_keeps_top_layer() {
+        let graph = parse_file("../datasets/tiny/cycle_8_shuffled.gr").unwrap();
+
+        for algorithm in Algorithm::value_variants() {
+            let solution = solve(&graph, algorithm, &SolveOptions::default());
+
+            // Edges are relabelled in place: the top endpoint of every edge must be untouched
+            for (before, after) in graph.edges.iter().zip(&solution.edges) {
+                assert_eq!(before.0, after.0, "{} moved a top node", algorithm);
+            }
+        }
+    }
+
+    #[test]
+    fn cancelled_solve_returns_a_solution() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/medium/58.gr").unwrap()).into();
+        let options = SolveOptions::default();
+        options.cancellation.cancel();
+
+        let start = Some(Algorithm::IterBarycenter.solver());
+        let solvers: [Arc<dyn Solver>; 4] = [
+            Algorithm::IterBarycenter.solver(),
+            Algorithm::BranchAndBound.solver(),
+            Arc::new(sifting::SiftingConfig {
+                start: start.clone(),
+            }),
+            Arc::new(simulated_annealing::SimulatedAnnealingConfig {
+                start,
+                ..Default::default()
+            }),
+        ];
+
+        for solver in solvers {
+            let mut solution = graph.clone();
+            let stats = solve_in_place(&mut solution, &*solver, &options);
+            assert_eq!(stats.status, ProofStatus::Feasible, "{}", solver.name());
+
+            let mut order = solution.bottom_order();
+            order.sort_unstable();
+            assert_eq!(
+                order,
+                (0..graph.bottom_nodes_abscissas.len()).collect::<Vec<_>>()
+            );
         }
     }
 }
@@ -0,0 +1,161 @@
+//! Post-optimization of a bottom order by exchanging adjacent nodes.
+//!
+//! Swapping two adjacent nodes `u, v` only changes the crossings between their own edges, from `c(u, v)` to `c(v, u)`.
+//! Both refinements only apply swaps that remove crossings, so they never increase the crossing count.
+
+use std::collections::BinaryHeap;
+
+use crate::{
+    algorithms::{solve_free_layers, FreeLayer, ProofStatus, Refinement},
+    crossing_matrix::{crossing_matrix, CrossingMatrix},
+    graphs::abscissa_graph::AbscissaGraph,
+};
+
+/// Refine the order of the free layer(s) of a graph. Returns the number of removed crossings.
+pub fn refine(graph: &mut AbscissaGraph, refinement: Refinement, free_layer: FreeLayer) -> u64 {
+    let mut removed = 0;
+
+    solve_free_layers(graph, free_layer, |layer_graph| {
+        let matrix = crossing_matrix(layer_graph);
+        let mut order = layer_graph.bottom_order();
+        removed += match refinement {
+            Refinement::AdjacentExchange => adjacent_exchange(&*matrix, &mut order),
+            Refinement::GreedySwitch => greedy_switch(&*matrix, &mut order),
+        };
+        layer_graph.set_bottom_order(&order);
+        ProofStatus::Feasible
+    });
+
+    removed
+}
+
+/// Bubble-sort-like passes over the order, swapping adjacent nodes whenever it removes crossings,
+/// until a pass does not swap anything. Returns the number of removed crossings.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(V)` crossing matrix queries per pass
+/// * Space: `O(1)`
+pub fn adjacent_exchange<M: CrossingMatrix + ?Sized>(matrix: &M, order: &mut [usize]) -> u64 {
+    let mut removed = 0;
+    let mut swapped = true;
+
+    while swapped {
+        swapped = false;
+        for i in 1..order.len() {
+            let (u, v) = (order[i - 1], order[i]);
+            let (uv, vu) = (matrix.crossings(u, v), matrix.crossings(v, u));
+            if vu < uv {
+                order.swap(i - 1, i);
+                removed += uv - vu;
+                swapped = true;
+            }
+        }
+    }
+
+    removed
+}
+
+/// Greedily apply the adjacent swap that removes the most crossings, until no swap removes crossings.
+/// Returns the number of removed crossings.
+///
+/// Algorithm
+/// ---------
+/// 1. Push the gain `c(u, v) - c(v, u)` of every adjacent pair with a positive gain in a max-heap.
+/// 2. Pop the best pair. Skip it if the order changed at this position since it was pushed.
+/// 3. Swap the pair, and push the new gains of the pairs on both sides of it.
+///
+/// Complexity
+/// ----------
+/// * Time: `O((V + S) * log(V + S))` where `S` is the number of swaps
+/// * Space: `O(V + S)`
+pub fn greedy_switch<M: CrossingMatrix + ?Sized>(matrix: &M, order: &mut [usize]) -> u64 {
+    let gain = |u: usize, v: usize| matrix.crossings(u, v) as i128 - matrix.crossings(v, u) as i128;
+
+    // (gain, position of the first node, first node, second node)
+    let mut heap: BinaryHeap<(i128, usize, usize, usize)> = (1..order.len())
+        .map(|i| (gain(order[i - 1], order[i]), i - 1, order[i - 1], order[i]))
+        .filter(|&(pair_gain, ..)| pair_gain > 0)
+        .collect();
+    let mut removed = 0;
+
+    while let Some((pair_gain, i, u, v)) = heap.pop() {
+        if order[i] != u || order[i + 1] != v {
+            continue; // Outdated pair
+        }
+
+        order.swap(i, i + 1);
+        removed += pair_gain as u64;
+
+        // The pairs on both sides of the swapped nodes changed
+        for j in [i.checked_sub(1), Some(i + 1)].into_iter().flatten() {
+            if j + 1 < order.len() {
+                let (a, b) = (order[j], order[j + 1]);
+                let new_gain = gain(a, b);
+                if new_gain > 0 {
+                    heap.push((new_gain, j, a, b));
+                }
+            }
+        }
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::{
+        algorithms::{solve_in_place, Algorithm, SolveOptions},
+        crossings::count_crossings,
+    };
+
+    #[test]
+    fn refinements_remove_crossings() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/medium/58.gr").unwrap()).into();
+        let mut median = graph.clone();
+        solve_in_place(&mut median, &Algorithm::Median, &SolveOptions::default());
+        let median_crossings = count_crossings(&median);
+        let matrix = crossing_matrix(&median);
+
+        for refine in [
+            adjacent_exchange::<dyn CrossingMatrix + Sync>,
+            greedy_switch,
+        ] {
+            let mut order = median.bottom_order();
+            let removed = refine(&*matrix, &mut order);
+
+            let mut refined = median.clone();
+            refined.set_bottom_order(&order);
+            assert_eq!(count_crossings(&refined), median_crossings - removed);
+
+            // No adjacent swap can remove crossings anymore
+            for pair in order.windows(2) {
+                assert!(matrix.crossings(pair[1], pair[0]) >= matrix.crossings(pair[0], pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn refinement_chains_after_any_algorithm() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/tiny/website_20.gr").unwrap()).into();
+
+        for algorithm in [Algorithm::Median, Algorithm::Barycenter] {
+            let mut unrefined = graph.clone();
+            solve_in_place(&mut unrefined, &algorithm, &SolveOptions::default());
+
+            for refinement in [Refinement::AdjacentExchange, Refinement::GreedySwitch] {
+                let options = SolveOptions {
+                    refinement: Some(refinement),
+                    ..Default::default()
+                };
+                let mut refined = graph.clone();
+                solve_in_place(&mut refined, &algorithm, &options);
+
+                assert!(count_crossings(&refined) <= count_crossings(&unrefined));
+            }
+        }
+    }
+}
@@ -1,86 +1,233 @@
-use ordered_float::OrderedFloat;
+use std::fmt::{Display, Formatter};
 
-use crate::{crossings::line_sweep_crossings, graphs::abscissa_graph::AbscissaGraph};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    algorithms::{
+        break_ties, constrain_bottom_order, FreeLayer, ProofStatus, SolveOptions, TieBreak,
+    },
+    cancellation::CancellationToken,
+    crossings::count_crossings,
+    events::{EventObserver, SolverEvent},
+    graphs::abscissa_graph::AbscissaGraph,
+    precedence::Precedence,
+    solver::{Solver, SolverStats},
+};
+
+/// Weighting of the neighbor abscissas in the barycenter of a node
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BarycenterKind {
+    /// Plain mean of the neighbor abscissas
+    #[default]
+    Mean,
+    /// Mean of the neighbor abscissas weighted by the degree of the neighbors,
+    /// so that the nodes with many edges pull their neighbors harder
+    DegreeWeighted,
+}
+
+impl Display for BarycenterKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BarycenterKind::Mean => write!(f, "mean"),
+            BarycenterKind::DegreeWeighted => write!(f, "degree_weighted"),
+        }
+    }
+}
+
+/// Configuration of the barycenter heuristic
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BarycenterConfig {
+    /// Iterate the heuristic until the crossing count stops decreasing
+    pub iterated: bool,
+    /// Weighting of the neighbor abscissas
+    pub kind: BarycenterKind,
+    /// Order of the nodes with the same barycenter
+    pub tie_break: TieBreak,
+    /// Seed of the random tie-breaking (random by default)
+    pub seed: Option<u64>,
+}
+
+impl Solver for BarycenterConfig {
+    fn name(&self) -> &str {
+        if self.iterated {
+            "iterated_barycenter"
+        } else {
+            "barycenter"
+        }
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        let mut parameters = vec![
+            ("kind", self.kind.to_string()),
+            ("tie_break", self.tie_break.to_string()),
+        ];
+        if self.tie_break == TieBreak::Random {
+            parameters.push((
+                "seed",
+                self.seed
+                    .map_or_else(|| "auto".to_string(), |seed| seed.to_string()),
+            ));
+        }
+        parameters
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+        if self.tie_break == TieBreak::Random {
+            options.observer.notify(SolverEvent::Seed { seed });
+        }
+        if !self.iterated {
+            barycenter_variant_solve(graph, options.free_layer, self, &mut rng);
+            constrain_bottom_order(graph, options.bottom_precedence());
+            return SolverStats::heuristic();
+        }
+
+        let iterations = iterated_barycenter_heuristic_solve(
+            graph,
+            options.free_layer,
+            options.bottom_precedence(),
+            self,
+            &mut rng,
+            &options.cancellation,
+            &options.observer,
+        );
+        SolverStats {
+            status: ProofStatus::Feasible,
+            iterations,
+        }
+    }
+}
 
 /// Do one in-place iteration of the barycenter heuristic method on a graph
+/// where all vertices have an abscissa, with the default configuration (see [`barycenter_variant_solve`]).
+pub fn barycenter_heuristic_solve(graph: &mut AbscissaGraph, free_layer: FreeLayer) {
+    barycenter_variant_solve(
+        graph,
+        free_layer,
+        &BarycenterConfig::default(),
+        &mut StdRng::seed_from_u64(0),
+    );
+}
+
+/// Do one in-place iteration of a variant of the barycenter heuristic method on a graph
 /// where all vertices have an abscissa.
 ///
 /// Algorithm
 /// ---------
-/// 1. For each node (top and bottom), set the new abscissa to the mean of its neighbors' abscissas.
+/// 1. For each node of the free layer(s), set the new abscissa to the mean of its neighbors' abscissas,
+///    weighted by the edge weights, and according to the configured kind.
 ///    * If the node has no neighbors, keep its abscissa.
+/// 2. Replace the new abscissas of each free layer by their ranks, ordering the nodes with the same barycenter
+///    with the tie-breaking policy (`rng` is only used by the random policy).
 ///
 /// Note: the abscissas must have to be rebalanced before displaying the graph again in order to have a pretty display.
 ///
 /// Complexity
 /// ----------
-/// * Time: `O(V + E)`
+/// * Time: `O(V * log(V) + E)`
 /// * Space: `O(V + E)`
-pub fn barycenter_heuristic_solve(graph: &mut AbscissaGraph) {
+pub fn barycenter_variant_solve<R: Rng>(
+    graph: &mut AbscissaGraph,
+    free_layer: FreeLayer,
+    config: &BarycenterConfig,
+    rng: &mut R,
+) {
     // Create vectors to be updated
     // Space complexity: O(V)
     let mut top_x = graph.top_nodes_abscissas.clone();
     let mut bottom_x = graph.bottom_nodes_abscissas.clone();
 
-    // Store neighbors indices for each node in a vector.
-    // Neighbors for each top node
-    // Space complexity: O(E) after filling
-    let mut top_neighbors: Vec<Vec<OrderedFloat<f64>>> =
-        vec![vec![]; graph.top_nodes_abscissas.len()];
-    // Neighbors for each bottom node
-    let mut bottom_neighbors: Vec<Vec<OrderedFloat<f64>>> =
-        vec![vec![]; graph.bottom_nodes_abscissas.len()];
-
-    // For each node, store its neighbors' abscissas in a vector
+    // Weighted degree of each node (an edge of weight w counts as w parallel edges)
     // Time complexity: O(E)
-    graph.edges.iter().for_each(|(top_index, bottom_index)| {
-        top_neighbors[*top_index as usize]
-            .push(graph.bottom_nodes_abscissas[*bottom_index as usize].into());
-        bottom_neighbors[*bottom_index as usize]
-            .push(graph.top_nodes_abscissas[*top_index as usize].into());
-    });
-
-    // For each top node, compute the mean of its neighbors' abscissas
-    // Time complexity: O(V)
-    top_neighbors
-        .iter_mut()
+    let mut top_degrees = vec![0_usize; graph.top_nodes_abscissas.len()];
+    let mut bottom_degrees = vec![0_usize; graph.bottom_nodes_abscissas.len()];
+    graph
+        .edges
+        .iter()
         .enumerate()
-        .for_each(|(index, neighbors)| {
-            if !neighbors.is_empty() {
-                top_x[index] =
-                    neighbors.iter().map(|x| x.into_inner()).sum::<f64>() / neighbors.len() as f64;
-            }
+        .for_each(|(index, (top_index, bottom_index))| {
+            top_degrees[*top_index as usize] += graph.weight(index) as usize;
+            bottom_degrees[*bottom_index as usize] += graph.weight(index) as usize;
         });
+    let weight = |degree: usize| match config.kind {
+        BarycenterKind::Mean => 1.0,
+        BarycenterKind::DegreeWeighted => degree as f64,
+    };
 
-    // For each bottom node, compute the mean of its neighbors' abscissas
-    // Time complexity: O(V)
-    bottom_neighbors
-        .iter_mut()
+    // Weighted sums of the neighbors' abscissas, and sums of their weights
+    // Space complexity: O(V)
+    let mut top_sums = vec![(0.0, 0.0); graph.top_nodes_abscissas.len()];
+    let mut bottom_sums = vec![(0.0, 0.0); graph.bottom_nodes_abscissas.len()];
+
+    // Time complexity: O(E)
+    graph
+        .edges
+        .iter()
         .enumerate()
-        .for_each(|(index, neighbors)| {
-            if !neighbors.is_empty() {
-                bottom_x[index] =
-                    neighbors.iter().map(|x| x.into_inner()).sum::<f64>() / neighbors.len() as f64;
-            }
+        .for_each(|(index, (top_index, bottom_index))| {
+            let (top, bottom) = (*top_index as usize, *bottom_index as usize);
+            let edge_weight = graph.weight(index) as f64;
+
+            let bottom_weight = edge_weight * weight(bottom_degrees[bottom]);
+            top_sums[top].0 += bottom_weight * graph.bottom_nodes_abscissas[bottom];
+            top_sums[top].1 += bottom_weight;
+
+            let top_weight = edge_weight * weight(top_degrees[top]);
+            bottom_sums[bottom].0 += top_weight * graph.top_nodes_abscissas[top];
+            bottom_sums[bottom].1 += top_weight;
         });
 
-    // Swap vectors
-    graph.top_nodes_abscissas = top_x;
-    graph.bottom_nodes_abscissas = bottom_x;
+    // For each node with neighbors, compute the weighted mean of its neighbors' abscissas
+    // Time complexity: O(V)
+    for (x, (sum, weights)) in top_x.iter_mut().zip(&top_sums) {
+        if *weights > 0.0 {
+            *x = sum / weights;
+        }
+    }
+    for (x, (sum, weights)) in bottom_x.iter_mut().zip(&bottom_sums) {
+        if *weights > 0.0 {
+            *x = sum / weights;
+        }
+    }
+
+    // Swap vectors, only for the layers that are allowed to move
+    if free_layer.top_is_free() {
+        break_ties(
+            &mut top_x,
+            &graph.top_nodes_abscissas,
+            &top_degrees,
+            config.tie_break,
+            rng,
+        );
+        graph.top_nodes_abscissas = top_x;
+    }
+    if free_layer.bottom_is_free() {
+        break_ties(
+            &mut bottom_x,
+            &graph.bottom_nodes_abscissas,
+            &bottom_degrees,
+            config.tie_break,
+            rng,
+        );
+        graph.bottom_nodes_abscissas = bottom_x;
+    }
 }
 
 /// Do multiple in-place iterations of the barycenter heuristic method on a graph
 /// where all vertices have an abscissa. Count the crossings before each iteration,
-/// and stop when the crossing count stops decreasing.
+/// and stop when the crossing count stops decreasing or when the cancellation token is cancelled.
+/// Progress is reported to the observer. Returns the number of iterations.
 ///
 /// Algorithm
 /// ---------
 /// 1. Count the crossings in the graph.
 ///
-/// 2. For each node (top and bottom), set the new abscissa to the mean of its neighbors' abscissas.
-///    * If the node has no neighbors, keep its abscissa.
+/// 2. For each node of the free layer(s), set the new abscissa to the mean of its neighbors' abscissas,
+///    see [`barycenter_variant_solve`].
 ///
-/// 3. Rebalance the graph node positions, and start again.
+/// 3. Rebalance the graph node positions, reorder the bottom layer to respect the precedence constraints if any,
+///    and start again.
 ///
 /// Note: the abscissas must have to be rebalanced before displaying the graph again in order to have a pretty display.
 ///
@@ -88,30 +235,100 @@ pub fn barycenter_heuristic_solve(graph: &mut AbscissaGraph) {
 /// ----------
 /// * Time: Depends on the number of iterations.
 /// * Space: `O(V + E)`
-pub fn iterated_barycenter_heuristic_solve(graph: &mut AbscissaGraph, verbose: bool) {
-    let mut new_crossings = line_sweep_crossings(graph);
+pub fn iterated_barycenter_heuristic_solve(
+    graph: &mut AbscissaGraph,
+    free_layer: FreeLayer,
+    precedence: Option<&Precedence>,
+    config: &BarycenterConfig,
+    rng: &mut impl Rng,
+    cancellation: &CancellationToken,
+    observer: &EventObserver,
+) -> u64 {
+    let mut new_crossings = count_crossings(graph);
     let mut crossings = new_crossings + 1;
     let mut iteration = 0;
     let mut previous_graph = graph.clone();
 
-    while new_crossings < crossings {
+    while new_crossings < crossings && !cancellation.is_cancelled() {
+        observer.notify(SolverEvent::IterationStarted {
+            iteration: iteration + 1,
+        });
         previous_graph = graph.clone(); // Save the previous graph (if the last iteration is not fruitful). This may be expensive
 
-        barycenter_heuristic_solve(graph);
+        barycenter_variant_solve(graph, free_layer, config, rng);
         graph.rebalance_abscissas(); // Rebalance the node positions, because we use means
+        constrain_bottom_order(graph, precedence);
 
         // Swap and recompute crossings
         crossings = new_crossings;
-        new_crossings = line_sweep_crossings(graph);
+        new_crossings = count_crossings(graph);
 
-        if verbose {
-            iteration += 1;
-            println!("Iteration {}: {} crossings", iteration, new_crossings);
+        iteration += 1;
+        observer.notify(SolverEvent::Crossings {
+            iteration,
+            crossings: new_crossings,
+        });
+        if new_crossings < crossings {
+            observer.notify(SolverEvent::Improved {
+                iteration,
+                crossings: new_crossings,
+            });
         }
     }
 
     // If the last iteration was not fruitful, revert to the previous graph
     if new_crossings > crossings {
         *graph = previous_graph;
+        observer.notify(SolverEvent::Reverted {
+            iteration,
+            crossings,
+        });
+    }
+
+    observer.notify(SolverEvent::Finished {
+        iterations: iteration,
+        crossings: new_crossings.min(crossings),
+    });
+    iteration
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn barycenter_kinds_and_tie_breaking() {
+        // Bottom nodes 0 to 3 have the neighbors {0, 2}, {1, 2}, {1, 2} and {1}.
+        // The top nodes 0, 1 and 2 have 1, 3 and 3 neighbors
+        let graph = AbscissaGraph {
+            top_nodes_abscissas: vec![0.0, 1.0, 2.0],
+            bottom_nodes_abscissas: vec![0.0, 2.0, 1.0, 3.0],
+            edges: vec![(0, 0), (2, 0), (1, 1), (2, 1), (1, 2), (2, 2), (1, 3)],
+            ..Default::default()
+        };
+        let solve_with = |kind: BarycenterKind| {
+            let config = BarycenterConfig {
+                kind,
+                ..Default::default()
+            };
+            let mut solution = graph.clone();
+            barycenter_variant_solve(
+                &mut solution,
+                FreeLayer::Bottom,
+                &config,
+                &mut StdRng::seed_from_u64(0),
+            );
+            solution.bottom_order()
+        };
+
+        // Means: 1, 1.5, 1.5 and 1. Tied nodes keep their previous order
+        assert_eq!(solve_with(BarycenterKind::Mean), vec![0, 3, 2, 1]);
+        // Weighted by degree: (0 * 1 + 2 * 3) / 4 = 1.5, (1 * 3 + 2 * 3) / 6 = 1.5, 1.5 and 1
+        assert_eq!(solve_with(BarycenterKind::DegreeWeighted), vec![3, 0, 2, 1]);
+
+        let mut reordered = graph.clone();
+        reordered.bottom_nodes_abscissas = vec![3.0, 0.0, 1.0, 2.0];
+        barycenter_heuristic_solve(&mut reordered, FreeLayer::Bottom);
+        assert_eq!(reordered.bottom_order(), vec![3, 0, 1, 2]);
     }
 }
@@ -0,0 +1,519 @@
+//! Exact branch and bound solver for the one-sided crossing minimization problem.
+//!
+//! The bottom order is built from left to right. Placing a node `v` fixes the cost `c(v, w)` of all its pairs
+//! with the nodes `w` that are not placed yet, and the remaining pairs cost at least `min(c(u, w), c(w, u))` each.
+
+use ahash::AHashMap;
+
+use crate::{
+    algorithms::{best_heuristic_order, solve_free_layers, ProofStatus, SolveOptions},
+    cancellation::CancellationToken,
+    crossing_matrix::{CrossingMatrix, DenseCrossingMatrix, DENSE_MATRIX_MAX_SIZE},
+    events::{EventObserver, SolverEvent},
+    graphs::abscissa_graph::{AbscissaGraph, WeightedRank},
+    precedence::Precedence,
+    solver::{Solver, SolverStats},
+};
+
+/// Maximum memory used by the placed node sets remembered to cut equivalent branches, in bytes
+const MAX_MEMO_BYTES: usize = 256 << 20;
+/// Number of search nodes between two checks of the cancellation token
+const CANCELLATION_CHECK_INTERVAL: u64 = 1 << 10;
+
+/// Limits of a search. The best solution found so far is returned when they are reached.
+#[derive(Debug, Clone, Default)]
+pub struct SearchBudget {
+    /// Maximum number of explored search nodes
+    pub node_limit: Option<u64>,
+    /// The search stops when this token is cancelled or reaches its deadline
+    pub cancellation: CancellationToken,
+}
+
+impl SearchBudget {
+    /// Budget starting now, with the time limit and cancellation token of the solver options
+    pub fn new(node_limit: Option<u64>, options: &SolveOptions) -> Self {
+        SearchBudget {
+            node_limit,
+            cancellation: options.cancellation.with_time_limit(options.time_limit),
+        }
+    }
+}
+
+/// Result of a branch and bound search
+#[derive(Debug, Clone)]
+pub struct SearchResult {
+    /// Best bottom order found, from left to right
+    pub order: Vec<usize>,
+    /// Number of crossings of the best order
+    pub crossings: u64,
+    /// Whether the search completed, proving that the order is optimal
+    pub status: ProofStatus,
+    /// Number of explored search nodes
+    pub nodes: u64,
+}
+
+/// Configuration of the branch and bound solver, which solves the problem exactly for the free layer(s) of a graph
+/// within the node and time limits. Graphs with more than [`DENSE_MATRIX_MAX_SIZE`] free nodes only get the
+/// best heuristic order.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BranchAndBoundConfig {
+    /// Maximum number of search nodes explored for each free layer
+    pub node_limit: Option<u64>,
+}
+
+impl Solver for BranchAndBoundConfig {
+    fn name(&self) -> &str {
+        "branch_and_bound"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![(
+            "node_limit",
+            self.node_limit
+                .map_or_else(|| "none".to_string(), |limit| limit.to_string()),
+        )]
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        let budget = SearchBudget::new(self.node_limit, options);
+        let mut iterations = 0;
+        let status = solve_free_layers(graph, options.free_layer, |layer_graph| {
+            let stats = solve_bottom_layer(
+                layer_graph,
+                options.bottom_precedence(),
+                &budget,
+                &options.observer,
+            );
+            iterations += stats.iterations;
+            stats.status
+        });
+        SolverStats { status, iterations }
+    }
+}
+
+/// Reorder the bottom layer of a graph, starting from the best of its current order and the heuristic orders.
+/// The search always respects the forced pairs, so only the heuristic orders need the precedence constraints
+fn solve_bottom_layer(
+    graph: &mut AbscissaGraph,
+    precedence: Option<&Precedence>,
+    budget: &SearchBudget,
+    observer: &EventObserver,
+) -> SolverStats {
+    // Upper bound from the heuristics
+    let initial_order = best_heuristic_order(graph, precedence);
+
+    if graph.bottom_nodes_abscissas.len() > DENSE_MATRIX_MAX_SIZE {
+        observer.notify(SolverEvent::Fallback {
+            nodes: graph.bottom_nodes_abscissas.len(),
+            max_nodes: DENSE_MATRIX_MAX_SIZE,
+        });
+        graph.set_bottom_order(&initial_order);
+        return SolverStats::heuristic();
+    }
+
+    let result = branch_and_bound(&graph.bottom_neighbors(), &initial_order, budget);
+    observer.notify(SolverEvent::Finished {
+        iterations: result.nodes,
+        crossings: result.crossings,
+    });
+
+    graph.set_bottom_order(&result.order);
+    SolverStats {
+        status: result.status,
+        iterations: result.nodes,
+    }
+}
+
+/// Find an optimal bottom order from the sorted top neighbor ranks of each bottom node.
+///
+/// Algorithm
+/// ---------
+/// 1. Reduction rules:
+///    * Isolated nodes never cross anything and are placed at the end.
+///    * If `c(u, v) = 0 < c(v, u)`, `u` comes before `v` in every optimal order.
+///    * Nodes with the same neighbors are interchangeable and are kept in index order.
+///
+/// 2. Depth-first search over the order prefixes, starting with the initial order as the best solution:
+///    * Prune when the prefix cost plus the lower bound `sum(min(c(u, w), c(w, u)))` of the remaining pairs
+///      is not better than the best solution.
+///    * Prune when swapping the last two placed nodes would remove crossings.
+///    * Prune when the same set of nodes was already placed with fewer or as many crossings.
+///    * Try the nodes that add the fewest crossings above their lower bound first.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(V^2 * E)` to build the crossing matrix, then exponential in the worst case, bounded by the budget
+/// * Space: `O(V^2)`, plus the memoized node sets
+pub fn branch_and_bound(
+    neighbors: &[Vec<WeightedRank>],
+    initial_order: &[usize],
+    budget: &SearchBudget,
+) -> SearchResult {
+    // Isolated nodes are left out of the search
+    let active: Vec<usize> = (0..neighbors.len())
+        .filter(|&u| !neighbors[u].is_empty())
+        .collect();
+    let mut local_index = vec![usize::MAX; neighbors.len()];
+    for (local, &u) in active.iter().enumerate() {
+        local_index[u] = local;
+    }
+
+    let matrix = DenseCrossingMatrix::from_neighbors(
+        &active
+            .iter()
+            .map(|&u| neighbors[u].clone())
+            .collect::<Vec<_>>(),
+    );
+    let mut search = Search::new(&matrix, budget);
+
+    // Forced pairs
+    for u in 0..search.size {
+        for v in 0..search.size {
+            if u != v && matrix.crossings(u, v) == 0 && matrix.crossings(v, u) > 0 {
+                search.add_constraint(u, v);
+            }
+        }
+    }
+
+    // Interchangeable nodes, in index order
+    let mut twins: AHashMap<&[WeightedRank], usize> = AHashMap::new();
+    for (local, &u) in active.iter().enumerate() {
+        if let Some(previous) = twins.insert(&neighbors[u], local) {
+            search.add_constraint(previous, local);
+        }
+    }
+
+    // The initial order is the solution to beat
+    let initial_local: Vec<usize> = initial_order
+        .iter()
+        .map(|&u| local_index[u])
+        .filter(|&local| local != usize::MAX)
+        .collect();
+    search.best_crossings = order_crossings(&matrix, &initial_local);
+
+    let lower_bound = search.row_min.iter().sum::<u64>() / 2;
+    if lower_bound < search.best_crossings {
+        search.explore(0, lower_bound);
+    }
+
+    let order = match &search.best_order {
+        Some(best_order) => best_order
+            .iter()
+            .map(|&local| active[local])
+            .chain((0..neighbors.len()).filter(|&u| neighbors[u].is_empty()))
+            .collect(),
+        None => initial_order.to_vec(),
+    };
+
+    SearchResult {
+        order,
+        crossings: search.best_crossings,
+        status: if search.aborted {
+            ProofStatus::Feasible
+        } else {
+            ProofStatus::Optimal
+        },
+        nodes: search.nodes,
+    }
+}
+
+/// Number of crossings of a bottom order, from the crossing matrix
+pub fn order_crossings<M: CrossingMatrix + ?Sized>(matrix: &M, order: &[usize]) -> u64 {
+    let mut crossings = 0;
+    for (position, &u) in order.iter().enumerate() {
+        for &v in &order[position + 1..] {
+            crossings += matrix.crossings(u, v);
+        }
+    }
+    crossings
+}
+
+/// Depth-first search state
+struct Search<'a> {
+    matrix: &'a DenseCrossingMatrix,
+    size: usize,
+    budget: &'a SearchBudget,
+
+    /// Nodes that must be placed after each node
+    successors: Vec<Vec<usize>>,
+    /// Number of nodes that must still be placed before each node
+    predecessors_left: Vec<usize>,
+
+    /// Current prefix, and the set of its nodes
+    prefix: Vec<usize>,
+    placed: Vec<bool>,
+    placed_bits: Vec<u64>,
+    /// For each node, the sum of `c(v, w)` over the nodes `w` not placed yet
+    row_cost: Vec<u64>,
+    /// For each node, the sum of `min(c(v, w), c(w, v))` over the nodes `w` not placed yet
+    row_min: Vec<u64>,
+
+    /// Lowest number of crossings found for each set of placed nodes
+    memo: AHashMap<Vec<u64>, u64>,
+    /// Number of memo entries fitting in `MAX_MEMO_BYTES`, as the keys grow with the block size
+    max_memo_entries: usize,
+
+    best_crossings: u64,
+    best_order: Option<Vec<usize>>,
+    nodes: u64,
+    aborted: bool,
+}
+
+impl<'a> Search<'a> {
+    fn new(matrix: &'a DenseCrossingMatrix, budget: &'a SearchBudget) -> Self {
+        let size = matrix.size();
+        let mut row_cost = vec![0; size];
+        let mut row_min = vec![0; size];
+        let key_words = size.div_ceil(64);
+        // Each entry holds its key words, the key vector header and the crossings
+        let entry_bytes = key_words * size_of::<u64>() + size_of::<Vec<u64>>() + size_of::<u64>();
+        for v in 0..size {
+            for w in 0..size {
+                if v != w {
+                    row_cost[v] += matrix.crossings(v, w);
+                    row_min[v] += matrix.crossings(v, w).min(matrix.crossings(w, v));
+                }
+            }
+        }
+
+        Search {
+            matrix,
+            size,
+            budget,
+            successors: vec![vec![]; size],
+            predecessors_left: vec![0; size],
+            prefix: Vec::with_capacity(size),
+            placed: vec![false; size],
+            placed_bits: vec![0; key_words],
+            row_cost,
+            row_min,
+            memo: AHashMap::new(),
+            max_memo_entries: MAX_MEMO_BYTES / entry_bytes,
+            best_crossings: u64::MAX,
+            best_order: None,
+            nodes: 0,
+            aborted: false,
+        }
+    }
+
+    /// Require `u` to be placed before `v`
+    fn add_constraint(&mut self, u: usize, v: usize) {
+        self.successors[u].push(v);
+        self.predecessors_left[v] += 1;
+    }
+
+    /// Whether the node limit is reached, or the search is cancelled
+    fn budget_exhausted(&self) -> bool {
+        self.budget
+            .node_limit
+            .is_some_and(|limit| self.nodes > limit)
+            || (self.nodes.is_multiple_of(CANCELLATION_CHECK_INTERVAL)
+                && self.budget.cancellation.is_cancelled())
+    }
+
+    /// Explore all the completions of the current prefix, which has `crossings` crossings.
+    /// `lower_bound` is the lower bound of the crossings between the nodes that are not placed yet.
+    fn explore(&mut self, crossings: u64, lower_bound: u64) {
+        self.nodes += 1;
+        if self.budget_exhausted() {
+            self.aborted = true;
+            return;
+        }
+
+        // Complete order, which is better than the best one thanks to the pruning below
+        if self.prefix.len() == self.size {
+            self.best_crossings = crossings;
+            self.best_order = Some(self.prefix.clone());
+            return;
+        }
+
+        // Cut the prefixes that place the same nodes with more crossings
+        if let Some(memo_crossings) = self.memo.get_mut(&self.placed_bits) {
+            if *memo_crossings <= crossings {
+                return;
+            }
+            *memo_crossings = crossings;
+        } else if self.memo.len() < self.max_memo_entries {
+            self.memo.insert(self.placed_bits.clone(), crossings);
+        }
+
+        let last = self.prefix.last().copied();
+        let mut candidates: Vec<(u64, usize)> = (0..self.size)
+            .filter(|&v| !self.placed[v] && self.predecessors_left[v] == 0)
+            .filter(|&v| {
+                last.is_none_or(|u| self.matrix.crossings(u, v) <= self.matrix.crossings(v, u))
+            })
+            .map(|v| (self.row_cost[v] - self.row_min[v], v))
+            .collect();
+        candidates.sort_unstable();
+
+        for (_, v) in candidates {
+            let (added, bound_removed) = (self.row_cost[v], self.row_min[v]);
+            if crossings + added + lower_bound - bound_removed >= self.best_crossings {
+                continue;
+            }
+
+            self.place(v);
+            self.explore(crossings + added, lower_bound - bound_removed);
+            self.unplace(v);
+
+            if self.aborted {
+                return;
+            }
+        }
+    }
+
+    /// Append a node to the prefix
+    fn place(&mut self, v: usize) {
+        self.placed[v] = true;
+        self.placed_bits[v / 64] |= 1 << (v % 64);
+        self.prefix.push(v);
+
+        for w in 0..self.size {
+            if !self.placed[w] {
+                let (wv, vw) = (self.matrix.crossings(w, v), self.matrix.crossings(v, w));
+                self.row_cost[w] -= wv;
+                self.row_min[w] -= wv.min(vw);
+            }
+        }
+        for &successor in &self.successors[v] {
+            self.predecessors_left[successor] -= 1;
+        }
+    }
+
+    /// Remove the last node of the prefix
+    fn unplace(&mut self, v: usize) {
+        for &successor in &self.successors[v] {
+            self.predecessors_left[successor] += 1;
+        }
+        for w in 0..self.size {
+            if !self.placed[w] {
+                let (wv, vw) = (self.matrix.crossings(w, v), self.matrix.crossings(v, w));
+                self.row_cost[w] += wv;
+                self.row_min[w] += wv.min(vw);
+            }
+        }
+
+        self.prefix.pop();
+        self.placed_bits[v / 64] &= !(1 << (v % 64));
+        self.placed[v] = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::{bipartite_graph::BipartiteGraph, parse_file};
+    use walkdir::WalkDir;
+
+    use super::*;
+    use crate::{
+        algorithms::{solve_in_place, Algorithm},
+        crossings::count_crossings,
+    };
+
+    /// Minimum number of crossings over all bottom orders, by enumerating them
+    fn brute_force_crossings(neighbors: &[Vec<WeightedRank>]) -> u64 {
+        fn permute(matrix: &DenseCrossingMatrix, order: &mut Vec<usize>, k: usize) -> u64 {
+            if k == order.len() {
+                return order_crossings(matrix, order);
+            }
+            let mut best = u64::MAX;
+            for i in k..order.len() {
+                order.swap(k, i);
+                best = best.min(permute(matrix, order, k + 1));
+                order.swap(k, i);
+            }
+            best
+        }
+
+        let matrix = DenseCrossingMatrix::from_neighbors(neighbors);
+        permute(&matrix, &mut (0..neighbors.len()).collect(), 0)
+    }
+
+    #[test]
+    fn branch_and_bound_matches_brute_force() {
+        // Small pseudo-random graphs with parallel edges and isolated nodes
+        let mut seed = 12345_u64;
+        let mut random = |max: u64| {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (seed >> 33) % max
+        };
+
+        for _ in 0..200 {
+            let bottom_count = 1 + random(7) as usize;
+            let top_count = 1 + random(6);
+            let neighbors: Vec<Vec<WeightedRank>> = (0..bottom_count)
+                .map(|_| {
+                    let mut n: Vec<WeightedRank> = (0..random(4))
+                        .map(|_| (random(top_count), 1 + random(3)))
+                        .collect();
+                    n.sort_unstable();
+                    n.dedup_by_key(|(rank, _)| *rank);
+                    n
+                })
+                .collect();
+
+            let initial_order: Vec<usize> = (0..bottom_count).collect();
+            let result = branch_and_bound(&neighbors, &initial_order, &SearchBudget::default());
+            let matrix = DenseCrossingMatrix::from_neighbors(&neighbors);
+
+            assert_eq!(result.status, ProofStatus::Optimal);
+            assert_eq!(result.crossings, brute_force_crossings(&neighbors));
+            assert_eq!(order_crossings(&matrix, &result.order), result.crossings);
+
+            let mut sorted_order = result.order.clone();
+            sorted_order.sort_unstable();
+            assert_eq!(sorted_order, initial_order);
+        }
+    }
+
+    #[test]
+    fn branch_and_bound_beats_heuristics() {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let filename = file.path().to_str().unwrap();
+            let graph: AbscissaGraph = (&parse_file(filename).unwrap()).into();
+
+            let mut exact = graph.clone();
+            let stats = solve_in_place(
+                &mut exact,
+                &Algorithm::BranchAndBound,
+                &SolveOptions::default(),
+            );
+            assert_eq!(stats.status, ProofStatus::Optimal, "{}", filename);
+
+            for algorithm in [Algorithm::IterMedian, Algorithm::IterBarycenter] {
+                let mut heuristic = graph.clone();
+                solve_in_place(&mut heuristic, &algorithm, &SolveOptions::default());
+                assert!(
+                    count_crossings(&exact) <= count_crossings(&heuristic),
+                    "{}: {} beats branch and bound",
+                    filename,
+                    algorithm
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn exhausted_budget_returns_feasible_solution() {
+        let graph = parse_file("../datasets/medium/58.gr").unwrap();
+        let mut abscissa_graph: AbscissaGraph = (&graph).into();
+
+        let config = BranchAndBoundConfig {
+            node_limit: Some(10),
+        };
+        let stats = config.solve(&mut abscissa_graph, &SolveOptions::default());
+        assert_eq!(stats.status, ProofStatus::Feasible);
+
+        // The solution is at least as good as the starting order
+        let solution: BipartiteGraph = (&abscissa_graph).into();
+        assert!(count_crossings(&solution) <= count_crossings(&graph));
+    }
+}
@@ -0,0 +1,249 @@
+//! Exact dynamic programming solver over the subsets of the free layer, for small graphs.
+
+use crate::{
+    algorithms::{
+        constrain_bottom_order, median_heuristic::median_heuristic_solve, solve_free_layers,
+        FreeLayer, ProofStatus, SolveOptions,
+    },
+    crossing_matrix::{CrossingMatrix, DenseCrossingMatrix},
+    crossings::order_crossings,
+    events::SolverEvent,
+    graphs::abscissa_graph::{AbscissaGraph, WeightedRank},
+    solver::{Solver, SolverStats},
+};
+
+/// Default maximum number of free nodes (the table takes `8 * 2^20` bytes = 8 MB)
+pub const DEFAULT_MAX_FREE_NODES: usize = 20;
+/// Memory budget of the table, which has one `u64` per subset of the free nodes (512 MB)
+const TABLE_MEMORY_BUDGET: usize = 512 << 20;
+/// Hard limit on the number of free nodes, above which the table would exceed its memory budget (26 nodes)
+const MAX_FREE_NODES: usize = (TABLE_MEMORY_BUDGET / size_of::<u64>()).ilog2() as usize;
+
+/// Configuration of the dynamic programming solver, which solves the problem exactly for the free layer(s) of a graph.
+/// Layers with more free nodes than the limit fall back to the median heuristic, unless the current order is better.
+#[derive(Debug, Clone, Copy)]
+pub struct ExactDpConfig {
+    /// Maximum number of non isolated free nodes (at most 26)
+    pub max_nodes: usize,
+}
+
+impl Default for ExactDpConfig {
+    fn default() -> Self {
+        ExactDpConfig {
+            max_nodes: DEFAULT_MAX_FREE_NODES,
+        }
+    }
+}
+
+impl Solver for ExactDpConfig {
+    fn name(&self) -> &str {
+        "exact_dp"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![("max_nodes", self.max_nodes.to_string())]
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        let mut iterations = 0;
+        let status = solve_free_layers(graph, options.free_layer, |layer_graph| {
+            iterations += 1;
+            let neighbors = layer_graph.bottom_neighbors();
+            match exact_dp(&neighbors, self.max_nodes) {
+                Some((order, crossings)) => {
+                    options.observer.notify(SolverEvent::Finished {
+                        iterations: 1,
+                        crossings,
+                    });
+                    layer_graph.set_bottom_order(&order);
+                    ProofStatus::Optimal
+                }
+                None => {
+                    options.observer.notify(SolverEvent::Fallback {
+                        nodes: neighbors.iter().filter(|n| !n.is_empty()).count(),
+                        max_nodes: self.max_nodes.min(MAX_FREE_NODES),
+                    });
+                    let initial_order = layer_graph.bottom_order();
+                    median_heuristic_solve(layer_graph, FreeLayer::Bottom);
+                    constrain_bottom_order(layer_graph, options.bottom_precedence());
+                    if order_crossings(&neighbors, &layer_graph.bottom_order())
+                        > order_crossings(&neighbors, &initial_order)
+                    {
+                        layer_graph.set_bottom_order(&initial_order);
+                    } else {
+                        layer_graph.rebalance_abscissas();
+                    }
+                    ProofStatus::Feasible
+                }
+            }
+        });
+        SolverStats { status, iterations }
+    }
+}
+
+/// Find an optimal bottom order from the sorted top neighbor ranks of each bottom node.
+/// Returns the order and its number of crossings, or `None` if there are more than `max_nodes` non isolated nodes.
+///
+/// Algorithm
+/// ---------
+/// 1. Isolated nodes never cross anything and are placed at the end.
+///
+/// 2. For each subset `S` of the other nodes, `f(S)` is the minimum number of crossings between the nodes of `S`
+///    when they are placed first. Placing `v` last among `S` adds the crossings `sum(c(u, v))` over `u` in `S \ {v}`:
+///    `f(S) = min(f(S \ {v}) + sum(c(u, v)))` over `v` in `S`.
+///    The sums are read in `O(1)` from two precomputed tables per node, one for each half of the subset bits.
+///
+/// 3. The optimal order is rebuilt backwards from the full set, by finding the node that was placed last.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(2^n * n)` where `n` is the number of non isolated nodes
+/// * Space: `O(2^n + n * 2^(n/2))`
+pub fn exact_dp(neighbors: &[Vec<WeightedRank>], max_nodes: usize) -> Option<(Vec<usize>, u64)> {
+    let active: Vec<usize> = (0..neighbors.len())
+        .filter(|&u| !neighbors[u].is_empty())
+        .collect();
+    let n = active.len();
+    if n > max_nodes.min(MAX_FREE_NODES) {
+        return None;
+    }
+
+    let matrix = DenseCrossingMatrix::from_neighbors(
+        &active
+            .iter()
+            .map(|&u| neighbors[u].clone())
+            .collect::<Vec<_>>(),
+    );
+
+    // Split tables: low[v][m] = sum(c(u, v)) for u in the low bits of m, and the same for the high bits
+    let low_bits = n / 2;
+    let high_bits = n - low_bits;
+    let low_mask = (1 << low_bits) - 1;
+    let half_sums = |offset: usize, bits: usize| -> Vec<Vec<u64>> {
+        (0..n)
+            .map(|v| {
+                let mut sums = vec![0; 1 << bits];
+                for mask in 1_usize..1 << bits {
+                    let lowest = mask.trailing_zeros() as usize;
+                    sums[mask] = sums[mask & (mask - 1)] + matrix.crossings(offset + lowest, v);
+                }
+                sums
+            })
+            .collect()
+    };
+    let low_sums = half_sums(0, low_bits);
+    let high_sums = half_sums(low_bits, high_bits);
+    let placement_cost =
+        |set: usize, v: usize| low_sums[v][set & low_mask] + high_sums[v][set >> low_bits];
+
+    // Time complexity: O(2^n * n)
+    let mut best = vec![u64::MAX; 1 << n];
+    best[0] = 0;
+    for set in 1_usize..1 << n {
+        let mut remaining = set;
+        while remaining != 0 {
+            let v = remaining.trailing_zeros() as usize;
+            remaining &= remaining - 1;
+
+            let previous = set & !(1 << v);
+            best[set] = best[set].min(best[previous] + placement_cost(previous, v));
+        }
+    }
+
+    // Rebuild the order backwards
+    let mut order = Vec::with_capacity(neighbors.len());
+    let mut set = (1_usize << n) - 1;
+    while set != 0 {
+        let v = (0..n)
+            .filter(|&v| set & (1 << v) != 0)
+            .find(|&v| {
+                let previous = set & !(1 << v);
+                best[previous] + placement_cost(previous, v) == best[set]
+            })
+            .unwrap();
+        order.push(active[v]);
+        set &= !(1 << v);
+    }
+    order.reverse();
+    order.extend((0..neighbors.len()).filter(|&u| neighbors[u].is_empty()));
+
+    Some((order, best[(1 << n) - 1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use walkdir::WalkDir;
+
+    use super::*;
+    use crate::{
+        algorithms::{branch_and_bound::order_crossings, solve_in_place, Algorithm},
+        crossings::count_crossings,
+    };
+
+    #[test]
+    fn exact_dp_is_an_oracle_for_the_heuristics() {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let filename = file.path().to_str().unwrap();
+            let graph: AbscissaGraph = (&ocm_parser::parse_file(filename).unwrap()).into();
+
+            let mut exact = graph.clone();
+            let stats = solve_in_place(&mut exact, &Algorithm::ExactDp, &SolveOptions::default());
+            assert_eq!(stats.status, ProofStatus::Optimal, "{}", filename);
+            let optimum = count_crossings(&exact);
+
+            // The crossing count of the returned order must match the one from the table
+            let neighbors = graph.bottom_neighbors();
+            let (order, crossings) = exact_dp(&neighbors, DEFAULT_MAX_FREE_NODES).unwrap();
+            let matrix = DenseCrossingMatrix::from_neighbors(&neighbors);
+            assert_eq!(order_crossings(&matrix, &order), crossings);
+            assert_eq!(crossings, optimum, "{}", filename);
+
+            for algorithm in [
+                Algorithm::Median,
+                Algorithm::IterMedian,
+                Algorithm::Barycenter,
+                Algorithm::IterBarycenter,
+            ] {
+                let mut heuristic = graph.clone();
+                solve_in_place(&mut heuristic, &algorithm, &SolveOptions::default());
+                assert!(
+                    optimum <= count_crossings(&heuristic),
+                    "{}: {} beats the optimum",
+                    filename,
+                    algorithm
+                );
+            }
+
+            let mut branch_and_bound = graph.clone();
+            solve_in_place(
+                &mut branch_and_bound,
+                &Algorithm::BranchAndBound,
+                &SolveOptions::default(),
+            );
+            assert_eq!(count_crossings(&branch_and_bound), optimum, "{}", filename);
+        }
+    }
+
+    #[test]
+    fn exact_dp_falls_back_above_size_limit() {
+        let graph = ocm_parser::parse_file("../datasets/tiny/website_20.gr").unwrap();
+        let mut abscissa_graph: AbscissaGraph = (&graph).into();
+
+        let config = ExactDpConfig { max_nodes: 2 };
+        let stats = config.solve(&mut abscissa_graph, &SolveOptions::default());
+        assert_eq!(stats.status, ProofStatus::Feasible);
+        assert!(exact_dp(&abscissa_graph.bottom_neighbors(), 2).is_none());
+        assert_eq!(MAX_FREE_NODES, 26);
+
+        // The fallback never makes an optimal starting order worse
+        let (order, optimum) = exact_dp(&abscissa_graph.bottom_neighbors(), 20).unwrap();
+        abscissa_graph.set_bottom_order(&order);
+        config.solve(&mut abscissa_graph, &SolveOptions::default());
+        assert_eq!(count_crossings(&abscissa_graph), optimum);
+    }
+}
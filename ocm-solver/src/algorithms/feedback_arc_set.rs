@@ -0,0 +1,281 @@
+//! Feedback arc set solver: the bottom order is a topological order of the penalty graph
+//! (see [`crate::penalty_graph`]) after the removal of a light set of arcs, found with the Eades-Lin-Smyth heuristic.
+//! The order can then be improved by a local search moving single nodes, which removes backward arcs.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use crate::{
+    algorithms::{
+        constrain_bottom_order, median_heuristic::median_heuristic_solve, sifting::sifting_pass,
+        solve_free_layers, FreeLayer, ProofStatus, SolveOptions,
+    },
+    cancellation::CancellationToken,
+    crossing_matrix::{
+        CrossingMatrix, DenseCrossingMatrix, SparseCrossingMatrix, DENSE_MATRIX_MAX_SIZE,
+    },
+    decomposition::interval_blocks,
+    events::SolverEvent,
+    graphs::abscissa_graph::{AbscissaGraph, WeightedRank},
+    penalty_graph::{PenaltyGraph, WeightedArc},
+    precedence::Precedence,
+    solver::{Solver, SolverStats},
+};
+
+/// Configuration of the feedback arc set solver, which orders the free layer(s) of a graph.
+/// The penalty graph of each independent block is built separately (in `O(n^2)` time and space for `n` nodes),
+/// and layers with a block of more nodes than the limit fall back to the median heuristic.
+#[derive(Debug, Clone, Copy)]
+pub struct FeedbackArcSetConfig {
+    /// Maximum number of nodes of an independent block
+    pub max_nodes: usize,
+    /// Improve the order of each block with sifting passes (insertion moves) until none removes crossings,
+    /// or until the time limit
+    pub local_search: bool,
+}
+
+impl Default for FeedbackArcSetConfig {
+    fn default() -> Self {
+        FeedbackArcSetConfig {
+            max_nodes: DENSE_MATRIX_MAX_SIZE,
+            local_search: true,
+        }
+    }
+}
+
+impl Solver for FeedbackArcSetConfig {
+    fn name(&self) -> &str {
+        "feedback_arc_set"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("max_nodes", self.max_nodes.to_string()),
+            ("local_search", self.local_search.to_string()),
+        ]
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        let cancellation = options.cancellation.with_time_limit(options.time_limit);
+        let mut iterations = 0;
+        let status = solve_free_layers(graph, options.free_layer, |layer_graph| {
+            iterations += 1;
+            let precedence = options.bottom_precedence();
+            let neighbors = layer_graph.bottom_neighbors();
+            let blocks = interval_blocks(&neighbors);
+
+            let largest_block = blocks.iter().map(Vec::len).max().unwrap_or(0);
+            if largest_block > self.max_nodes {
+                options.observer.notify(SolverEvent::Fallback {
+                    nodes: largest_block,
+                    max_nodes: self.max_nodes,
+                });
+                median_heuristic_solve(layer_graph, FreeLayer::Bottom);
+                layer_graph.rebalance_abscissas();
+                constrain_bottom_order(layer_graph, precedence);
+                return ProofStatus::Feasible;
+            }
+
+            // Blocks are sorted from left to right and do not cross each other. Isolated nodes go last
+            let mut order = Vec::with_capacity(neighbors.len());
+            for block in &blocks {
+                let block_neighbors: Vec<Vec<WeightedRank>> =
+                    block.iter().map(|&u| neighbors[u].clone()).collect();
+                let block_precedence = precedence.map(|precedence| precedence.subset(block));
+                let block_precedence = block_precedence.as_ref();
+                let block_order = if block.len() <= DENSE_MATRIX_MAX_SIZE {
+                    let matrix = DenseCrossingMatrix::from_neighbors(&block_neighbors);
+                    self.solve_block(&matrix, block_precedence, &cancellation)
+                } else {
+                    let matrix = SparseCrossingMatrix::from_neighbors(block_neighbors);
+                    self.solve_block(&matrix, block_precedence, &cancellation)
+                };
+                order.extend(block_order.into_iter().map(|index| block[index]));
+            }
+            order.extend((0..neighbors.len()).filter(|&u| neighbors[u].is_empty()));
+
+            layer_graph.set_bottom_order(&order);
+            ProofStatus::Feasible
+        });
+        SolverStats { status, iterations }
+    }
+}
+
+impl FeedbackArcSetConfig {
+    /// Order the nodes of an independent block from its crossing matrix, respecting the precedence constraints
+    /// between them if any
+    fn solve_block<M: CrossingMatrix>(
+        &self,
+        matrix: &M,
+        precedence: Option<&Precedence>,
+        cancellation: &CancellationToken,
+    ) -> Vec<usize> {
+        let mut order = eades_lin_smyth(&PenaltyGraph::from_matrix(matrix));
+        if let Some(precedence) = precedence {
+            order = precedence.linear_extension(&order);
+        }
+        if self.local_search {
+            loop {
+                let removed = sifting_pass(matrix, &mut order, precedence, cancellation);
+                if removed == 0 || cancellation.is_cancelled() {
+                    break;
+                }
+            }
+        }
+        order
+    }
+}
+
+/// Order the nodes of a weighted digraph so that the total weight of the backward arcs is small,
+/// with the Eades-Lin-Smyth heuristic. The order is a topological order if the graph is acyclic.
+///
+/// Algorithm
+/// ---------
+/// Repeatedly remove nodes from the remaining graph:
+/// 1. Sinks are removed first and prepended to the end of the order.
+/// 2. Then sources, which are appended to the start of the order.
+/// 3. Otherwise, the node with the largest difference between its outgoing and incoming arc weights
+///    is appended to the start of the order. Its incoming arcs are the backward arcs.
+///
+/// Complexity
+/// ----------
+/// * Time: `O((V + A) * log(V + A))` where `A` is the number of arcs
+/// * Space: `O(V + A)`
+pub fn eades_lin_smyth(graph: &PenaltyGraph) -> Vec<usize> {
+    let size = graph.size();
+    let mut out_degree: Vec<usize> = (0..size).map(|u| graph.successors(u).len()).collect();
+    let mut in_degree: Vec<usize> = (0..size).map(|u| graph.predecessors(u).len()).collect();
+    let weight_sum =
+        |arcs: &[WeightedArc]| -> i128 { arcs.iter().map(|(_, weight)| *weight as i128).sum() };
+    // Outgoing minus incoming arc weights of each node in the remaining graph
+    let mut balance: Vec<i128> = (0..size)
+        .map(|u| weight_sum(graph.successors(u)) - weight_sum(graph.predecessors(u)))
+        .collect();
+    let mut removed = vec![false; size];
+
+    let mut sinks: Vec<usize> = (0..size).filter(|&u| out_degree[u] == 0).collect();
+    let mut sources: Vec<usize> = (0..size).filter(|&u| in_degree[u] == 0).collect();
+    // Lazy max-heap of (balance, node), outdated entries are skipped. Ties go to the smallest node
+    let mut heap: BinaryHeap<(i128, Reverse<usize>)> =
+        (0..size).map(|u| (balance[u], Reverse(u))).collect();
+
+    let (mut start, mut end) = (Vec::with_capacity(size), Vec::new());
+    let mut remaining = size;
+
+    while remaining > 0 {
+        let (u, at_end) = if let Some(u) = sinks.pop() {
+            (u, true)
+        } else if let Some(u) = sources.pop() {
+            (u, false)
+        } else {
+            let (_, Reverse(u)) = std::iter::from_fn(|| heap.pop())
+                .find(|&(node_balance, Reverse(u))| !removed[u] && node_balance == balance[u])
+                .unwrap();
+            (u, false)
+        };
+        if removed[u] {
+            continue;
+        }
+
+        removed[u] = true;
+        remaining -= 1;
+        if at_end {
+            end.push(u);
+        } else {
+            start.push(u);
+        }
+
+        for &(v, weight) in graph.successors(u) {
+            if !removed[v] {
+                in_degree[v] -= 1;
+                balance[v] += weight as i128;
+                heap.push((balance[v], Reverse(v)));
+                if in_degree[v] == 0 {
+                    sources.push(v);
+                }
+            }
+        }
+        for &(v, weight) in graph.predecessors(u) {
+            if !removed[v] {
+                out_degree[v] -= 1;
+                balance[v] -= weight as i128;
+                heap.push((balance[v], Reverse(v)));
+                if out_degree[v] == 0 {
+                    sinks.push(v);
+                }
+            }
+        }
+    }
+
+    start.extend(end.into_iter().rev());
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::parse_file;
+    use walkdir::WalkDir;
+
+    use super::*;
+    use crate::{
+        algorithms::{exact_dp::exact_dp, solve_in_place},
+        crossings::count_crossings,
+    };
+
+    #[test]
+    fn feedback_arc_set_orders_acyclic_penalty_graphs_optimally() {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let graph: AbscissaGraph = (&parse_file(file.path().to_str().unwrap()).unwrap()).into();
+            let penalty_graph = PenaltyGraph::new(&graph);
+            let order = eades_lin_smyth(&penalty_graph);
+
+            let mut sorted = order.clone();
+            sorted.sort_unstable();
+            assert_eq!(
+                sorted,
+                (0..graph.bottom_nodes_abscissas.len()).collect::<Vec<_>>()
+            );
+
+            let mut solution = graph.clone();
+            let stats = solve_in_place(
+                &mut solution,
+                &FeedbackArcSetConfig::default(),
+                &SolveOptions::default(),
+            );
+            assert_eq!(stats.status, ProofStatus::Feasible);
+
+            // The order has no backward arc when the optimum reaches the base crossings
+            let (_, optimum) = exact_dp(&graph.bottom_neighbors(), 20).unwrap();
+            if optimum == penalty_graph.base_crossings() {
+                assert_eq!(
+                    penalty_graph.crossings(&order),
+                    optimum,
+                    "{}",
+                    file.path().display()
+                );
+                assert_eq!(
+                    count_crossings(&solution),
+                    optimum,
+                    "{}",
+                    file.path().display()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn feedback_arc_set_beats_the_current_order() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/medium/68.gr").unwrap()).into();
+        let mut solution = graph.clone();
+        solve_in_place(
+            &mut solution,
+            &FeedbackArcSetConfig::default(),
+            &SolveOptions::default(),
+        );
+        assert!(count_crossings(&solution) < count_crossings(&graph));
+    }
+}
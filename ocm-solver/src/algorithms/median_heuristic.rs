@@ -1,18 +1,132 @@
+use std::fmt::{Display, Formatter};
+
 use ordered_float::OrderedFloat;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use crate::{
-    algo_utils::sorted_array_median, crossings::line_sweep_crossings,
+    algo_utils::sorted_array_interpolated_median,
+    algorithms::{
+        break_ties, constrain_bottom_order, FreeLayer, ProofStatus, SolveOptions, TieBreak,
+    },
+    cancellation::CancellationToken,
+    crossings::count_crossings,
+    events::{EventObserver, SolverEvent},
     graphs::abscissa_graph::AbscissaGraph,
+    precedence::Precedence,
+    solver::{Solver, SolverStats},
 };
 
+/// Median of the neighbor abscissas of a node with an even number of neighbors
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum MedianKind {
+    /// Average of the two middle values
+    #[default]
+    Average,
+    /// Lower middle value
+    Left,
+    /// Upper middle value
+    Right,
+    /// Two middle values interpolated towards the denser side (Gansner et al.)
+    Weighted,
+}
+
+impl Display for MedianKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MedianKind::Average => write!(f, "average"),
+            MedianKind::Left => write!(f, "left"),
+            MedianKind::Right => write!(f, "right"),
+            MedianKind::Weighted => write!(f, "weighted"),
+        }
+    }
+}
+
+/// Configuration of the median heuristic
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MedianConfig {
+    /// Iterate the heuristic until the crossing count stops decreasing
+    pub iterated: bool,
+    /// Median of the nodes with an even number of neighbors
+    pub kind: MedianKind,
+    /// Order of the nodes with the same median
+    pub tie_break: TieBreak,
+    /// Seed of the random tie-breaking (random by default)
+    pub seed: Option<u64>,
+}
+
+impl Solver for MedianConfig {
+    fn name(&self) -> &str {
+        if self.iterated {
+            "iterated_median"
+        } else {
+            "median"
+        }
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        let mut parameters = vec![
+            ("kind", self.kind.to_string()),
+            ("tie_break", self.tie_break.to_string()),
+        ];
+        if self.tie_break == TieBreak::Random {
+            parameters.push((
+                "seed",
+                self.seed
+                    .map_or_else(|| "auto".to_string(), |seed| seed.to_string()),
+            ));
+        }
+        parameters
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+        if self.tie_break == TieBreak::Random {
+            options.observer.notify(SolverEvent::Seed { seed });
+        }
+        if !self.iterated {
+            median_variant_solve(graph, options.free_layer, self, &mut rng);
+            constrain_bottom_order(graph, options.bottom_precedence());
+            return SolverStats::heuristic();
+        }
+
+        let iterations = iterated_median_heuristic_solve(
+            graph,
+            options.free_layer,
+            options.bottom_precedence(),
+            self,
+            &mut rng,
+            &options.cancellation,
+            &options.observer,
+        );
+        SolverStats {
+            status: ProofStatus::Feasible,
+            iterations,
+        }
+    }
+}
+
 /// Do one in-place iteration of the median heuristic method on a graph
+/// where all vertices have an abscissa, with the default configuration (see [`median_variant_solve`]).
+pub fn median_heuristic_solve(graph: &mut AbscissaGraph, free_layer: FreeLayer) {
+    median_variant_solve(
+        graph,
+        free_layer,
+        &MedianConfig::default(),
+        &mut StdRng::seed_from_u64(0),
+    );
+}
+
+/// Do one in-place iteration of a variant of the median heuristic method on a graph
 /// where all vertices have an abscissa.
 ///
 /// Algorithm
 /// ---------
-/// 1. For each node (top and bottom), set the new abscissa to the median of its neighbors' abscissas.
+/// 1. For each node of the free layer(s), set the new abscissa to the median of its neighbors' abscissas.
 ///    * If the node has no neighbors, keep its abscissa.
-///    * If the node has an even number of neighbors, take the average of the two middle values.
+///    * If the node has an even number of neighbors, take the median of the configured kind.
+/// 2. Replace the new abscissas of each free layer by their ranks, ordering the nodes with the same median
+///    with the tie-breaking policy (`rng` is only used by the random policy).
 ///
 /// Note: the abscissas must have to be rebalanced before displaying the graph again in order to have a pretty display.
 ///
@@ -20,7 +134,12 @@ use crate::{
 /// ----------
 /// * Time: `O(V * log(V) + E)`
 /// * Space: `O(V + E)`
-pub fn median_heuristic_solve(graph: &mut AbscissaGraph) {
+pub fn median_variant_solve<R: Rng>(
+    graph: &mut AbscissaGraph,
+    free_layer: FreeLayer,
+    config: &MedianConfig,
+    rng: &mut R,
+) {
     // Create vectors to be updated
     // Space complexity: O(V)
     let mut top_x = graph.top_nodes_abscissas.clone();
@@ -29,20 +148,29 @@ pub fn median_heuristic_solve(graph: &mut AbscissaGraph) {
     // Store neighbors indices for each node in a vector.
     // Neighbors for each top node
     // Space complexity: O(E) after filling
-    let mut top_neighbors: Vec<Vec<OrderedFloat<f64>>> =
+    let mut top_neighbors: Vec<Vec<(OrderedFloat<f64>, u64)>> =
         vec![vec![]; graph.top_nodes_abscissas.len()];
     // Neighbors for each bottom node
-    let mut bottom_neighbors: Vec<Vec<OrderedFloat<f64>>> =
+    let mut bottom_neighbors: Vec<Vec<(OrderedFloat<f64>, u64)>> =
         vec![vec![]; graph.bottom_nodes_abscissas.len()];
 
-    // For each node, store its neighbors' abscissas in a vector
+    // For each node, store its neighbors' abscissas in a vector, with the edge weights
     // Time complexity: O(E)
-    graph.edges.iter().for_each(|(top_index, bottom_index)| {
-        top_neighbors[*top_index as usize]
-            .push(graph.bottom_nodes_abscissas[*bottom_index as usize].into());
-        bottom_neighbors[*bottom_index as usize]
-            .push(graph.top_nodes_abscissas[*top_index as usize].into());
-    });
+    graph
+        .edges
+        .iter()
+        .enumerate()
+        .for_each(|(index, (top_index, bottom_index))| {
+            let weight = graph.weight(index);
+            top_neighbors[*top_index as usize].push((
+                graph.bottom_nodes_abscissas[*bottom_index as usize].into(),
+                weight,
+            ));
+            bottom_neighbors[*bottom_index as usize].push((
+                graph.top_nodes_abscissas[*top_index as usize].into(),
+                weight,
+            ));
+        });
 
     // For each top node, sort its neighbors' abscissas and get the median
     // Time complexity: O(V * log(V))
@@ -52,8 +180,8 @@ pub fn median_heuristic_solve(graph: &mut AbscissaGraph) {
         .for_each(|(index, neighbors)| {
             neighbors.sort_unstable();
 
-            if let Some(median) = sorted_array_median(neighbors) {
-                top_x[index] = median.into();
+            if let Some(median) = median(neighbors, config.kind) {
+                top_x[index] = median;
             }
         });
 
@@ -65,29 +193,88 @@ pub fn median_heuristic_solve(graph: &mut AbscissaGraph) {
         .for_each(|(index, neighbors)| {
             neighbors.sort_unstable();
 
-            if let Some(median) = sorted_array_median(neighbors) {
-                bottom_x[index] = median.into();
+            if let Some(median) = median(neighbors, config.kind) {
+                bottom_x[index] = median;
             }
         });
 
-    // Swap vectors
-    graph.top_nodes_abscissas = top_x;
-    graph.bottom_nodes_abscissas = bottom_x;
+    // Swap vectors, only for the layers that are allowed to move
+    if free_layer.top_is_free() {
+        let degrees: Vec<usize> = top_neighbors.iter().map(|n| weighted_degree(n)).collect();
+        break_ties(
+            &mut top_x,
+            &graph.top_nodes_abscissas,
+            &degrees,
+            config.tie_break,
+            rng,
+        );
+        graph.top_nodes_abscissas = top_x;
+    }
+    if free_layer.bottom_is_free() {
+        let degrees: Vec<usize> = bottom_neighbors
+            .iter()
+            .map(|n| weighted_degree(n))
+            .collect();
+        break_ties(
+            &mut bottom_x,
+            &graph.bottom_nodes_abscissas,
+            &degrees,
+            config.tie_break,
+            rng,
+        );
+        graph.bottom_nodes_abscissas = bottom_x;
+    }
+}
+
+/// Sum of the edge weights of a node
+fn weighted_degree(neighbors: &[(OrderedFloat<f64>, u64)]) -> usize {
+    neighbors.iter().map(|(_, weight)| *weight as usize).sum()
+}
+
+/// Median of sorted neighbor abscissas with their edge weights, `None` if there are none.
+/// An edge of weight `w` counts as `w` parallel edges, i.e. its neighbor is repeated `w` times.
+fn median(neighbors: &[(OrderedFloat<f64>, u64)], kind: MedianKind) -> Option<f64> {
+    let len = weighted_degree(neighbors);
+    // Abscissa at a position of the sorted neighbors, with the repetitions
+    let at = |position: usize| {
+        let mut end = 0;
+        neighbors
+            .iter()
+            .find(|(_, weight)| {
+                end += *weight as usize;
+                end > position
+            })
+            .map(|(x, _)| x.into_inner())
+            .unwrap()
+    };
+
+    match kind {
+        _ if len == 0 => None,
+        MedianKind::Left => Some(at((len - 1) / 2)),
+        MedianKind::Right => Some(at(len / 2)),
+        _ if len % 2 == 1 => Some(at(len / 2)),
+        MedianKind::Average => Some((at(len / 2 - 1) + at(len / 2)) / 2.0),
+        // The interpolated median only depends on the extreme and middle values
+        MedianKind::Weighted => {
+            sorted_array_interpolated_median(&[at(0), at(len / 2 - 1), at(len / 2), at(len - 1)])
+        }
+    }
 }
 
 /// Do multiple in-place iterations of the median heuristic method on a graph
 /// where all vertices have an abscissa. Count the crossings before each iteration,
-/// and stop when the crossing count stops decreasing.
+/// and stop when the crossing count stops decreasing or when the cancellation token is cancelled.
+/// Progress is reported to the observer. Returns the number of iterations.
 ///
 /// Algorithm
 /// ---------
 /// 1. Count the crossings in the graph.
 ///
-/// 2. For each node (top and bottom), set the new abscissa to the median of its neighbors' abscissas.
-///    * If the node has no neighbors, keep its abscissa.
-///    * If the node has an even number of neighbors, take the average of the two middle values.
+/// 2. For each node of the free layer(s), set the new abscissa to the median of its neighbors' abscissas,
+///    see [`median_variant_solve`].
 ///
-/// 3. Rebalance the graph node positions, and start again.
+/// 3. Rebalance the graph node positions, reorder the bottom layer to respect the precedence constraints if any,
+///    and start again.
 ///
 /// Note: the abscissas must have to be rebalanced before displaying the graph again in order to have a pretty display.
 ///
@@ -95,30 +282,147 @@ pub fn median_heuristic_solve(graph: &mut AbscissaGraph) {
 /// ----------
 /// * Time: Depends on the number of iterations.
 /// * Space: `O(V + E)`
-pub fn iterated_median_heuristic_solve(graph: &mut AbscissaGraph, verbose: bool) {
-    let mut new_crossings = line_sweep_crossings(graph);
+pub fn iterated_median_heuristic_solve(
+    graph: &mut AbscissaGraph,
+    free_layer: FreeLayer,
+    precedence: Option<&Precedence>,
+    config: &MedianConfig,
+    rng: &mut impl Rng,
+    cancellation: &CancellationToken,
+    observer: &EventObserver,
+) -> u64 {
+    let mut new_crossings = count_crossings(graph);
     let mut crossings = new_crossings + 1;
     let mut iteration = 0;
     let mut previous_graph = graph.clone();
 
-    while new_crossings < crossings {
+    while new_crossings < crossings && !cancellation.is_cancelled() {
+        observer.notify(SolverEvent::IterationStarted {
+            iteration: iteration + 1,
+        });
         previous_graph = graph.clone(); // Save the previous graph (if the last iteration is not fruitful). This may be expensive
 
-        median_heuristic_solve(graph);
+        median_variant_solve(graph, free_layer, config, rng);
         graph.rebalance_abscissas(); // Rebalance the node positions, because we use medians
+        constrain_bottom_order(graph, precedence);
 
         // Swap and recompute crossings
         crossings = new_crossings;
-        new_crossings = line_sweep_crossings(graph);
+        new_crossings = count_crossings(graph);
 
-        if verbose {
-            iteration += 1;
-            println!("Iteration {}: {} crossings", iteration, new_crossings);
+        iteration += 1;
+        observer.notify(SolverEvent::Crossings {
+            iteration,
+            crossings: new_crossings,
+        });
+        if new_crossings < crossings {
+            observer.notify(SolverEvent::Improved {
+                iteration,
+                crossings: new_crossings,
+            });
         }
     }
 
     // If the last iteration was not fruitful, revert to the previous graph
     if new_crossings > crossings {
         *graph = previous_graph;
+        observer.notify(SolverEvent::Reverted {
+            iteration,
+            crossings,
+        });
+    }
+
+    observer.notify(SolverEvent::Finished {
+        iterations: iteration,
+        crossings: new_crossings.min(crossings),
+    });
+    iteration
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Bottom nodes 0, 1 and 2 with the neighbors {0, 1, 2}, {1} and {0, 2}: all their medians are 1
+    fn tied_graph() -> AbscissaGraph {
+        AbscissaGraph {
+            top_nodes_abscissas: vec![0.0, 1.0, 2.0],
+            bottom_nodes_abscissas: vec![0.0, 1.0, 2.0],
+            edges: vec![(0, 0), (1, 0), (2, 0), (1, 1), (0, 2), (2, 2)],
+            ..Default::default()
+        }
+    }
+
+    fn solve_with(kind: MedianKind, tie_break: TieBreak, seed: u64) -> Vec<usize> {
+        let config = MedianConfig {
+            kind,
+            tie_break,
+            ..Default::default()
+        };
+        let mut graph = tied_graph();
+        median_variant_solve(
+            &mut graph,
+            FreeLayer::Bottom,
+            &config,
+            &mut StdRng::seed_from_u64(seed),
+        );
+        graph.bottom_order()
+    }
+
+    #[test]
+    fn tie_breaking_policies_are_deterministic() {
+        assert_eq!(
+            solve_with(MedianKind::Average, TieBreak::KeepPrevious, 0),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            solve_with(MedianKind::Average, TieBreak::Degree, 0),
+            vec![1, 2, 0]
+        );
+
+        let random = solve_with(MedianKind::Average, TieBreak::Random, 7);
+        assert_eq!(random, solve_with(MedianKind::Average, TieBreak::Random, 7));
+        let mut sorted = random.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, vec![0, 1, 2]);
+
+        // The previous order is kept whatever it is
+        let mut graph = tied_graph();
+        graph.bottom_nodes_abscissas = vec![2.0, 0.0, 1.0];
+        median_heuristic_solve(&mut graph, FreeLayer::Bottom);
+        assert_eq!(graph.bottom_order(), vec![1, 2, 0]);
+    }
+
+    #[test]
+    fn median_kinds_of_even_neighborhoods() {
+        let neighbors: Vec<(OrderedFloat<f64>, u64)> = [0.0, 1.0, 2.0, 10.0]
+            .into_iter()
+            .map(|x| (x.into(), 1))
+            .collect();
+        assert_eq!(median(&neighbors, MedianKind::Average), Some(1.5));
+        assert_eq!(median(&neighbors, MedianKind::Left), Some(1.0));
+        assert_eq!(median(&neighbors, MedianKind::Right), Some(2.0));
+        // Interpolated towards the left half, which is denser
+        assert_eq!(median(&neighbors, MedianKind::Weighted), Some(10.0 / 9.0));
+        assert_eq!(median(&neighbors[..3], MedianKind::Weighted), Some(1.0));
+        assert_eq!(median(&[], MedianKind::Left), None);
+
+        // Weighted edges count as parallel edges
+        let weighted = [(0.0.into(), 1), (1.0.into(), 1), (2.0.into(), 3)];
+        assert_eq!(median(&weighted, MedianKind::Average), Some(2.0));
+        let weighted = [(0.0.into(), 2), (10.0.into(), 2)];
+        assert_eq!(median(&weighted, MedianKind::Average), Some(5.0));
+        assert_eq!(median(&weighted, MedianKind::Left), Some(0.0));
+        assert_eq!(median(&weighted, MedianKind::Weighted), Some(5.0));
+
+        // The left and right medians split the nodes 1 and 2 of the tied graph
+        assert_eq!(
+            solve_with(MedianKind::Left, TieBreak::KeepPrevious, 0),
+            vec![2, 0, 1]
+        );
+        assert_eq!(
+            solve_with(MedianKind::Right, TieBreak::KeepPrevious, 0),
+            vec![0, 1, 2]
+        );
     }
 }
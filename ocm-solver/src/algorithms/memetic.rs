@@ -0,0 +1,392 @@
+//! Memetic algorithm over the bottom orders: a genetic algorithm whose offspring are improved by a local search.
+//!
+//! The population is seeded with the starting order, the median and barycenter heuristic orders and random orders.
+//! At each generation, parents chosen by tournament are recombined with the order crossover and mutated,
+//! then the offspring are improved and evaluated in parallel. The best distinct orders survive.
+
+use std::sync::Arc;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::{
+    algorithms::{
+        adjacent_exchange::adjacent_exchange, barycenter_heuristic::barycenter_heuristic_solve,
+        local_search_start_name, median_heuristic::median_heuristic_solve, sifting::sifting_pass,
+        solve_free_layers, FreeLayer, ProofStatus, SolveOptions,
+    },
+    cancellation::CancellationToken,
+    crossing_matrix::{crossing_matrix, CrossingMatrix},
+    crossings::order_crossings,
+    events::SolverEvent,
+    graphs::abscissa_graph::{AbscissaGraph, WeightedRank},
+    precedence::Precedence,
+    solver::{Solver, SolverStats},
+};
+
+/// Probability that an offspring is mutated by moving one of its nodes
+const MUTATION_RATE: f64 = 0.2;
+/// Number of individuals drawn at random to select a parent
+const TOURNAMENT_SIZE: usize = 2;
+
+/// Local search applied to each new individual
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LocalImprovement {
+    /// Passes swapping adjacent nodes whenever it removes crossings, see [`adjacent_exchange`]
+    #[default]
+    AdjacentExchange,
+    /// Sifting passes until no node can be moved to a better position (slower, but stronger)
+    Sifting,
+}
+
+/// Configuration of the memetic algorithm, which improves the order of the free layer(s) of a graph.
+///
+/// The search stops after the given number of generations, when the time limit is reached (split between
+/// the layers when both are free), or when it is cancelled. The best order found is always returned.
+#[derive(Debug, Clone)]
+pub struct MemeticConfig {
+    /// Solver building the starting order, which is one of the initial individuals
+    pub start: Option<Arc<dyn Solver>>,
+    /// Number of individuals kept after each generation (at least 2)
+    pub population_size: usize,
+    /// Maximum number of generations
+    pub generations: u64,
+    /// Seed of the random number generator (random by default)
+    pub seed: Option<u64>,
+    /// Local search applied to the initial individuals and to each offspring
+    pub local_improvement: LocalImprovement,
+}
+
+impl Default for MemeticConfig {
+    fn default() -> Self {
+        MemeticConfig {
+            start: None,
+            population_size: 20,
+            generations: 100,
+            seed: None,
+            local_improvement: LocalImprovement::default(),
+        }
+    }
+}
+
+/// Bottom order of the population, with its number of crossings
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Individual {
+    order: Vec<usize>,
+    crossings: u64,
+}
+
+impl Solver for MemeticConfig {
+    fn name(&self) -> &str {
+        "memetic"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("start", local_search_start_name(&self.start)),
+            ("population_size", self.population_size.to_string()),
+            ("generations", self.generations.to_string()),
+            (
+                "seed",
+                self.seed
+                    .map_or_else(|| "auto".to_string(), |seed| seed.to_string()),
+            ),
+            (
+                "local_improvement",
+                match self.local_improvement {
+                    LocalImprovement::AdjacentExchange => "adjacent_exchange",
+                    LocalImprovement::Sifting => "sifting",
+                }
+                .to_string(),
+            ),
+        ]
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        if let Some(start) = &self.start {
+            start.solve(graph, options);
+        }
+
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+        options.observer.notify(SolverEvent::Seed { seed });
+
+        let layer_time_limit = match options.free_layer {
+            FreeLayer::Both => options.time_limit.map(|limit| limit / 2),
+            _ => options.time_limit,
+        };
+        let mut generations = 0;
+
+        solve_free_layers(graph, options.free_layer, |layer_graph| {
+            let cancellation = options.cancellation.with_time_limit(layer_time_limit);
+
+            let mut median_graph = layer_graph.clone();
+            median_heuristic_solve(&mut median_graph, FreeLayer::Bottom);
+            let mut barycenter_graph = layer_graph.clone();
+            barycenter_heuristic_solve(&mut barycenter_graph, FreeLayer::Bottom);
+            let seeds = vec![
+                layer_graph.bottom_order(),
+                median_graph.bottom_order(),
+                barycenter_graph.bottom_order(),
+            ];
+
+            let neighbors = layer_graph.bottom_neighbors();
+            let matrix = crossing_matrix(layer_graph);
+            let (order, layer_generations) = memetic_search(
+                &*matrix,
+                &neighbors,
+                seeds,
+                self,
+                &cancellation,
+                options,
+                &mut rng,
+            );
+            generations += layer_generations;
+
+            layer_graph.set_bottom_order(&order);
+            ProofStatus::Feasible
+        });
+
+        SolverStats {
+            status: ProofStatus::Feasible,
+            iterations: generations,
+        }
+    }
+}
+
+/// Run the memetic algorithm from seed orders, completed with random orders up to the population size.
+/// Returns the best order found and the number of generations. The best crossing count of each generation
+/// is sent to the observer of the options. With precedence constraints, every individual is replaced by
+/// its linear extension before its local improvement.
+///
+/// Algorithm
+/// ---------
+/// 1. Improve and evaluate the initial individuals in parallel, and keep the best distinct ones.
+/// 2. Breed as many offspring as there are individuals: pick two parents by tournament, recombine them with
+///    the order crossover, and mutate the child with a random node move.
+/// 3. Improve and evaluate the offspring in parallel, and keep the best distinct individuals of the parents and offspring.
+/// 4. Repeat from 2 until the last generation, or until the search is cancelled.
+///
+/// The offspring are drawn sequentially from the random number generator, so a seed gives reproducible results
+/// whatever the number of threads.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(P * (V + E * log(V)))` per generation plus the local searches, where `P` is the population size
+/// * Space: `O(P * V)`
+fn memetic_search<M: CrossingMatrix + Sync + ?Sized, R: Rng>(
+    matrix: &M,
+    neighbors: &[Vec<WeightedRank>],
+    seeds: Vec<Vec<usize>>,
+    config: &MemeticConfig,
+    cancellation: &CancellationToken,
+    options: &SolveOptions,
+    rng: &mut R,
+) -> (Vec<usize>, u64) {
+    let population_size = config.population_size.max(2);
+    let precedence = options.bottom_precedence();
+    let improve = |orders: Vec<Vec<usize>>| -> Vec<Individual> {
+        orders
+            .into_par_iter()
+            .map(|mut order| {
+                if let Some(precedence) = precedence {
+                    order = precedence.linear_extension(&order);
+                }
+                improve_order(
+                    matrix,
+                    &mut order,
+                    precedence,
+                    config.local_improvement,
+                    cancellation,
+                );
+                let crossings = order_crossings(neighbors, &order);
+                Individual { order, crossings }
+            })
+            .collect()
+    };
+
+    let mut initial = seeds;
+    while initial.len() < population_size {
+        let mut order = initial[0].clone();
+        order.shuffle(rng);
+        initial.push(order);
+    }
+    let mut population = improve(initial);
+    select_survivors(&mut population, population_size);
+
+    let mut generation = 0;
+    while generation < config.generations && !cancellation.is_cancelled() {
+        generation += 1;
+        options.observer.notify(SolverEvent::IterationStarted {
+            iteration: generation,
+        });
+
+        let offspring: Vec<Vec<usize>> = (0..population_size)
+            .map(|_| {
+                let first = tournament(&population, rng);
+                let second = tournament(&population, rng);
+                let mut child = order_crossover(&first.order, &second.order, rng);
+                if rng.gen::<f64>() < MUTATION_RATE {
+                    mutate(&mut child, rng);
+                }
+                child
+            })
+            .collect();
+
+        let best_crossings = population[0].crossings;
+        population.extend(improve(offspring));
+        select_survivors(&mut population, population_size);
+
+        let crossings = population[0].crossings;
+        options.observer.notify(SolverEvent::Crossings {
+            iteration: generation,
+            crossings,
+        });
+        if crossings < best_crossings {
+            options.observer.notify(SolverEvent::Improved {
+                iteration: generation,
+                crossings,
+            });
+        }
+    }
+
+    let best = population.swap_remove(0);
+    options.observer.notify(SolverEvent::Finished {
+        iterations: generation,
+        crossings: best.crossings,
+    });
+
+    (best.order, generation)
+}
+
+/// Apply the local improvement to an order that respects the precedence constraints, if any.
+/// The sifting stops early when the search is cancelled
+fn improve_order<M: CrossingMatrix + ?Sized>(
+    matrix: &M,
+    order: &mut Vec<usize>,
+    precedence: Option<&Precedence>,
+    local_improvement: LocalImprovement,
+    cancellation: &CancellationToken,
+) {
+    match local_improvement {
+        LocalImprovement::AdjacentExchange => {
+            adjacent_exchange(matrix, order);
+        }
+        LocalImprovement::Sifting => loop {
+            let removed = sifting_pass(matrix, order, precedence, cancellation);
+            if removed == 0 || cancellation.is_cancelled() {
+                break;
+            }
+        },
+    }
+}
+
+/// Sort the population by crossings, remove the duplicate orders and keep the best individuals
+fn select_survivors(population: &mut Vec<Individual>, population_size: usize) {
+    population.sort_unstable_by(|a, b| {
+        a.crossings
+            .cmp(&b.crossings)
+            .then_with(|| a.order.cmp(&b.order))
+    });
+    population.dedup();
+    population.truncate(population_size);
+}
+
+/// Best individual among a few drawn at random
+fn tournament<'a, R: Rng>(population: &'a [Individual], rng: &mut R) -> &'a Individual {
+    (0..TOURNAMENT_SIZE)
+        .map(|_| &population[rng.gen_range(0..population.len())])
+        .min_by_key(|individual| individual.crossings)
+        .unwrap()
+}
+
+/// Order crossover: the child copies a random segment of the first parent at the same positions,
+/// and the other nodes in the order in which they appear in the second parent.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(V)`
+/// * Space: `O(V)`
+fn order_crossover<R: Rng>(first: &[usize], second: &[usize], rng: &mut R) -> Vec<usize> {
+    let size = first.len();
+    let (mut start, mut end) = (rng.gen_range(0..=size), rng.gen_range(0..=size));
+    if start > end {
+        (start, end) = (end, start);
+    }
+
+    let mut in_segment = vec![false; size];
+    first[start..end].iter().for_each(|&u| in_segment[u] = true);
+    let mut others = second.iter().copied().filter(|&u| !in_segment[u]);
+
+    let mut child = Vec::with_capacity(size);
+    child.extend(others.by_ref().take(start));
+    child.extend_from_slice(&first[start..end]);
+    child.extend(others);
+    child
+}
+
+/// Move a random node to a random position
+fn mutate<R: Rng>(order: &mut Vec<usize>, rng: &mut R) {
+    if order.len() < 2 {
+        return;
+    }
+    let node = order.remove(rng.gen_range(0..order.len()));
+    order.insert(rng.gen_range(0..=order.len()), node);
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::{
+        algorithms::{best_heuristic_order, solve_in_place},
+        crossings::count_crossings,
+    };
+
+    #[test]
+    fn order_crossover_builds_permutations() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let first: Vec<usize> = (0..20).collect();
+        let second: Vec<usize> = (0..20).rev().collect();
+
+        for _ in 0..100 {
+            let mut child = order_crossover(&first, &second, &mut rng);
+            mutate(&mut child, &mut rng);
+            child.sort_unstable();
+            assert_eq!(child, first);
+        }
+    }
+
+    #[test]
+    fn memetic_improves_the_heuristics_reproducibly() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/medium/68.gr").unwrap()).into();
+        let mut heuristic = graph.clone();
+        heuristic.set_bottom_order(&best_heuristic_order(&graph, None));
+
+        for local_improvement in [
+            LocalImprovement::AdjacentExchange,
+            LocalImprovement::Sifting,
+        ] {
+            let solver = MemeticConfig {
+                population_size: 8,
+                generations: 5,
+                seed: Some(42),
+                local_improvement,
+                ..Default::default()
+            };
+
+            let solutions: Vec<AbscissaGraph> = (0..2)
+                .map(|_| {
+                    let mut solution = graph.clone();
+                    let stats = solve_in_place(&mut solution, &solver, &SolveOptions::default());
+                    assert_eq!(stats.iterations, 5);
+                    solution
+                })
+                .collect();
+
+            assert_eq!(solutions[0].bottom_order(), solutions[1].bottom_order());
+            assert!(count_crossings(&solutions[0]) <= count_crossings(&heuristic));
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Portfolio of algorithms run in parallel on the same graph, keeping the solution with the fewest crossings.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    algorithms::{Algorithm, ProofStatus, SolveOptions},
+    crossings::count_crossings,
+    events::SolverEvent,
+    graphs::abscissa_graph::AbscissaGraph,
+    solver::{Solver, SolverStats},
+};
+
+/// Algorithms of the default portfolio
+pub const DEFAULT_PORTFOLIO: [Algorithm; 4] = [
+    Algorithm::Median,
+    Algorithm::IterMedian,
+    Algorithm::Barycenter,
+    Algorithm::IterBarycenter,
+];
+
+/// Configuration of the portfolio solver, which runs several solvers in parallel from the current order
+/// and keeps the best solution
+#[derive(Debug, Clone)]
+pub struct PortfolioConfig {
+    /// Solvers of the portfolio
+    pub solvers: Vec<Arc<dyn Solver>>,
+}
+
+impl Default for PortfolioConfig {
+    fn default() -> Self {
+        PortfolioConfig {
+            solvers: DEFAULT_PORTFOLIO
+                .iter()
+                .map(|algorithm| algorithm.solver())
+                .collect(),
+        }
+    }
+}
+
+/// Result of a solver of the portfolio
+#[derive(Debug, Clone)]
+pub struct PortfolioEntry {
+    /// Name of the solver
+    pub name: String,
+    /// Number of crossings of its solution, counted again after solving
+    pub crossings: u64,
+    /// Time spent by the solver
+    pub elapsed_time: Duration,
+    /// Statistics reported by the solver
+    pub stats: SolverStats,
+}
+
+/// Results of all the solvers of a portfolio
+#[derive(Debug, Clone)]
+pub struct PortfolioReport {
+    /// Result of each solver, in the order of the portfolio
+    pub entries: Vec<PortfolioEntry>,
+    /// Index of the entry whose solution was kept, if the portfolio is not empty
+    pub best: Option<usize>,
+}
+
+impl Solver for PortfolioConfig {
+    fn name(&self) -> &str {
+        "portfolio"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        let names: Vec<&str> = self.solvers.iter().map(|solver| solver.name()).collect();
+        vec![("solvers", names.join(","))]
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        let report = run_portfolio(graph, &self.solvers, options);
+
+        for (index, entry) in report.entries.iter().enumerate() {
+            options
+                .observer
+                .with_source(&entry.name)
+                .notify(SolverEvent::MemberFinished {
+                    crossings: entry.crossings,
+                    elapsed_time: entry.elapsed_time,
+                    status: entry.stats.status,
+                    kept: report.best == Some(index),
+                });
+        }
+
+        SolverStats {
+            status: report.best.map_or(ProofStatus::Feasible, |best| {
+                report.entries[best].stats.status
+            }),
+            iterations: report
+                .entries
+                .iter()
+                .map(|entry| entry.stats.iterations)
+                .sum(),
+        }
+    }
+}
+
+/// Run each solver on a copy of a graph in parallel, count the crossings of each solution,
+/// and keep the solution with the fewest crossings (the first one in portfolio order in case of a tie,
+/// unless another one is proven optimal). The solvers report their progress to the observer concurrently,
+/// each one with its name as the source of its events (see [`EventObserver::with_source`](crate::events::EventObserver::with_source)).
+pub fn run_portfolio(
+    graph: &mut AbscissaGraph,
+    solvers: &[Arc<dyn Solver>],
+    options: &SolveOptions,
+) -> PortfolioReport {
+    let results: Vec<(AbscissaGraph, PortfolioEntry)> = solvers
+        .par_iter()
+        .map(|solver| {
+            let solver_options = SolveOptions {
+                observer: options.observer.with_source(solver.name()),
+                ..options.clone()
+            };
+            let mut solution = graph.clone();
+            let start_time = Instant::now();
+            let stats = solver.solve(&mut solution, &solver_options);
+            let elapsed_time = start_time.elapsed();
+
+            let entry = PortfolioEntry {
+                name: solver.name().to_string(),
+                crossings: count_crossings(&solution),
+                elapsed_time,
+                stats,
+            };
+            (solution, entry)
+        })
+        .collect();
+
+    let best = results
+        .iter()
+        .enumerate()
+        .min_by_key(|(index, (_, entry))| {
+            (
+                entry.crossings,
+                entry.stats.status != ProofStatus::Optimal,
+                *index,
+            )
+        })
+        .map(|(index, _)| index);
+
+    let (solutions, entries): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+    if let Some(best) = best {
+        *graph = solutions.into_iter().nth(best).unwrap();
+    }
+
+    PortfolioReport { entries, best }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::{algorithms::solve_in_place, events::EventObserver};
+
+    #[test]
+    fn portfolio_keeps_the_best_solution() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/medium/58.gr").unwrap()).into();
+        let solvers: Vec<Arc<dyn Solver>> = [
+            Algorithm::Median,
+            Algorithm::Barycenter,
+            Algorithm::IterBarycenter,
+            Algorithm::Sifting,
+        ]
+        .iter()
+        .map(|algorithm| algorithm.solver())
+        .collect();
+
+        let mut solution = graph.clone();
+        let report = run_portfolio(&mut solution, &solvers, &SolveOptions::default());
+        assert_eq!(report.entries.len(), solvers.len());
+
+        for (solver, entry) in solvers.iter().zip(&report.entries) {
+            let mut single = graph.clone();
+            solve_in_place(&mut single, &**solver, &SolveOptions::default());
+            assert_eq!(entry.name, solver.name());
+            assert_eq!(entry.crossings, count_crossings(&single), "{}", entry.name);
+        }
+
+        let best = report.entries.iter().map(|entry| entry.crossings).min();
+        assert_eq!(Some(count_crossings(&solution)), best);
+        assert_eq!(
+            report.entries[report.best.unwrap()].crossings,
+            best.unwrap()
+        );
+    }
+
+    #[test]
+    fn portfolio_events_are_tagged_with_the_member_names() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/medium/58.gr").unwrap()).into();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&events);
+        let options = SolveOptions {
+            observer: EventObserver::new(move |source: Option<&str>, event: &SolverEvent| {
+                recorder
+                    .lock()
+                    .unwrap()
+                    .push((source.map(str::to_string), *event))
+            }),
+            ..Default::default()
+        };
+
+        let mut solution = graph.clone();
+        PortfolioConfig::default().solve(&mut solution, &options);
+        let events = events.lock().unwrap();
+
+        // Both iterated heuristics report their iterations, under their own name
+        for name in ["iterated_median", "iterated_barycenter"] {
+            assert!(events.iter().any(|(source, event)| {
+                source.as_deref() == Some(name) && matches!(event, SolverEvent::Crossings { .. })
+            }));
+        }
+
+        // One result per member, and only the best solution is kept
+        let results: Vec<(Option<String>, u64, bool)> = events
+            .iter()
+            .filter_map(|(source, event)| match event {
+                SolverEvent::MemberFinished {
+                    crossings, kept, ..
+                } => Some((source.clone(), *crossings, *kept)),
+                _ => None,
+            })
+            .collect();
+        let names: Vec<Option<String>> = DEFAULT_PORTFOLIO
+            .iter()
+            .map(|algorithm| Some(algorithm.solver().name().to_string()))
+            .collect();
+        assert_eq!(
+            results
+                .iter()
+                .map(|(name, ..)| name.clone())
+                .collect::<Vec<_>>(),
+            names
+        );
+        assert_eq!(results.iter().filter(|(.., kept)| *kept).count(), 1);
+        assert!(results
+            .iter()
+            .all(|(_, crossings, kept)| !kept || *crossings == count_crossings(&solution)));
+    }
+
+    #[test]
+    fn empty_portfolio_keeps_the_graph() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/tiny/website_20.gr").unwrap()).into();
+        let mut solution = graph.clone();
+        let report = run_portfolio(&mut solution, &[], &SolveOptions::default());
+
+        assert!(report.best.is_none());
+        assert_eq!(solution.bottom_order(), graph.bottom_order());
+    }
+}
@@ -0,0 +1,230 @@
+//! Sifting local search: each bottom node is moved in turn to its best position, the other nodes being fixed.
+
+use std::sync::Arc;
+
+use crate::{
+    algorithms::{
+        constrain_bottom_order, local_search_start_name, solve_free_layers, ProofStatus,
+        SolveOptions,
+    },
+    cancellation::CancellationToken,
+    crossing_matrix::{crossing_matrix, CrossingMatrix, DENSE_MATRIX_MAX_SIZE},
+    crossings::count_crossings,
+    events::SolverEvent,
+    graphs::abscissa_graph::AbscissaGraph,
+    precedence::Precedence,
+    solver::{Solver, SolverStats},
+};
+
+/// Configuration of the sifting local search, which improves the order of the free layer(s) of a graph.
+/// The search stops when a whole pass does not remove any crossing, when the time limit is reached,
+/// or when the search is cancelled.
+///
+/// Above [`DENSE_MATRIX_MAX_SIZE`] free nodes, the crossings are computed on demand by a sparse crossing matrix,
+/// and each pass takes more than `O(V^2)` time when the neighborhoods overlap (see [`sifting_pass`]): use a time limit on large graphs.
+#[derive(Debug, Clone, Default)]
+pub struct SiftingConfig {
+    /// Solver building the starting order (the current order by default)
+    pub start: Option<Arc<dyn Solver>>,
+}
+
+impl Solver for SiftingConfig {
+    fn name(&self) -> &str {
+        "sifting"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![("start", local_search_start_name(&self.start))]
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        if let Some(start) = &self.start {
+            start.solve(graph, options);
+        }
+        let cancellation = options.cancellation.with_time_limit(options.time_limit);
+        let mut pass = 0;
+
+        solve_free_layers(graph, options.free_layer, |layer_graph| {
+            let precedence = options.bottom_precedence();
+            constrain_bottom_order(layer_graph, precedence);
+            let nodes = layer_graph.bottom_nodes_abscissas.len();
+            if nodes > DENSE_MATRIX_MAX_SIZE {
+                options
+                    .observer
+                    .notify(SolverEvent::SparseCrossingMatrix { nodes });
+            }
+            let matrix = crossing_matrix(layer_graph);
+            let mut order = layer_graph.bottom_order();
+            let mut crossings = count_crossings(layer_graph);
+            let mut layer_pass = 0;
+
+            loop {
+                let removed = sifting_pass(&*matrix, &mut order, precedence, &cancellation);
+                crossings = crossings.saturating_sub(removed);
+                layer_pass += 1;
+
+                options.observer.notify(SolverEvent::Crossings {
+                    iteration: layer_pass,
+                    crossings,
+                });
+                if removed == 0 || cancellation.is_cancelled() {
+                    break;
+                }
+            }
+            pass += layer_pass;
+
+            layer_graph.set_bottom_order(&order);
+            ProofStatus::Feasible
+        });
+
+        SolverStats {
+            status: ProofStatus::Feasible,
+            iterations: pass,
+        }
+    }
+}
+
+/// Move each node of a bottom order to its best position, in the order in which they appear at the start of the pass.
+/// The pass stops early when the search is cancelled. Returns the number of removed crossings.
+/// With precedence constraints, the order must respect them, and each node stays inside its insertion window.
+///
+/// Algorithm
+/// ---------
+/// For each node `v`:
+/// 1. Remove `v` from the order.
+/// 2. Compute `c(w, v) - c(v, w)` for all nodes `w` at once (see [`CrossingMatrix::crossing_deltas`]).
+/// 3. Sweep the insertion positions from left to right. Moving `v` from before `w` to after `w`
+///    changes the crossing count by `c(w, v) - c(v, w)`, which gives the relative cost of every position.
+/// 4. Insert `v` at the position with the lowest cost inside its insertion window. Ties keep the current position.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(V^2)` with a [`DenseCrossingMatrix`](crate::crossing_matrix::DenseCrossingMatrix).
+///   With a [`SparseCrossingMatrix`](crate::crossing_matrix::SparseCrossingMatrix), the deltas of `v` are computed
+///   in one sweep over the edges overlapping its neighborhood, so a pass takes `O(V^2 + V * E_o)` time,
+///   where `E_o` is the largest number of edges of the nodes overlapping the neighborhood of a node.
+/// * Space: `O(V)`
+pub fn sifting_pass<M: CrossingMatrix + ?Sized>(
+    matrix: &M,
+    order: &mut Vec<usize>,
+    precedence: Option<&Precedence>,
+    cancellation: &CancellationToken,
+) -> u64 {
+    let mut removed = 0;
+    let mut deltas = vec![0; matrix.size()];
+
+    for v in order.clone() {
+        if cancellation.is_cancelled() {
+            break;
+        }
+
+        let position = order.iter().position(|&u| u == v).unwrap();
+        order.remove(position);
+        matrix.crossing_deltas(v, &mut deltas);
+
+        let window = precedence.map_or(0..=order.len(), |precedence| {
+            precedence.insertion_window(order, v)
+        });
+
+        // Cost of each position relative to the first one
+        let mut cost = 0_i128;
+        let mut current_cost = 0_i128;
+        let (mut best_cost, mut best_position) = (i128::MAX, position);
+        for insertion in 0..=order.len() {
+            if insertion > 0 {
+                cost = cost.saturating_add(deltas[order[insertion - 1]]);
+            }
+            if insertion == position {
+                current_cost = cost;
+            }
+            if window.contains(&insertion) && cost < best_cost {
+                (best_cost, best_position) = (cost, insertion);
+            }
+        }
+
+        if best_cost < current_cost {
+            order.insert(best_position, v);
+            removed += u64::try_from(current_cost - best_cost).unwrap_or(u64::MAX);
+        } else {
+            order.insert(position, v);
+        }
+    }
+
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::parse_file;
+    use walkdir::WalkDir;
+
+    use super::*;
+    use crate::{
+        algorithms::{solve_in_place, Algorithm},
+        crossing_matrix::{DenseCrossingMatrix, SparseCrossingMatrix},
+    };
+
+    #[test]
+    fn sifting_improves_its_start() {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .chain(WalkDir::new("../datasets/medium/58.gr"))
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let filename = file.path().to_str().unwrap();
+            let graph: AbscissaGraph = (&parse_file(filename).unwrap()).into();
+
+            for start in [None, Some(Algorithm::Median), Some(Algorithm::Barycenter)] {
+                let mut start_graph = graph.clone();
+                if let Some(start) = &start {
+                    solve_in_place(&mut start_graph, start, &SolveOptions::default());
+                }
+
+                let config = SiftingConfig {
+                    start: start.map(|start| start.solver()),
+                };
+                let mut sifted = graph.clone();
+                solve_in_place(&mut sifted, &config, &SolveOptions::default());
+
+                assert!(
+                    count_crossings(&sifted) <= count_crossings(&start_graph),
+                    "{}: sifting from {:?} added crossings",
+                    filename,
+                    start
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sifting_pass_counts_removed_crossings() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/tiny/website_20.gr").unwrap()).into();
+        let matrix = crossing_matrix(&graph);
+        let mut order = graph.bottom_order();
+
+        let removed = sifting_pass(&*matrix, &mut order, None, &CancellationToken::new());
+
+        let mut sifted = graph.clone();
+        sifted.set_bottom_order(&order);
+        assert_eq!(count_crossings(&graph) - removed, count_crossings(&sifted));
+    }
+
+    #[test]
+    fn sifting_pass_handles_saturated_crossings() {
+        // c(0, 1) saturates at u64::MAX and c(1, 0) = 0: node 1 must move before node 0
+        let neighbors = vec![vec![(1, u64::MAX)], vec![(0, 2)]];
+        let matrices: [Box<dyn CrossingMatrix>; 2] = [
+            Box::new(DenseCrossingMatrix::from_neighbors(&neighbors)),
+            Box::new(SparseCrossingMatrix::from_neighbors(neighbors.clone())),
+        ];
+
+        for matrix in matrices {
+            let mut order = vec![0, 1];
+            let removed = sifting_pass(&*matrix, &mut order, None, &CancellationToken::new());
+            assert_eq!(order, vec![1, 0]);
+            assert_eq!(removed, u64::MAX);
+        }
+    }
+}
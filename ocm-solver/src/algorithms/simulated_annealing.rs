@@ -0,0 +1,494 @@
+//! Simulated annealing over the bottom orders, an anytime metaheuristic for large instances.
+//!
+//! The search starts from the best heuristic order, and tries random moves from three neighborhoods:
+//! swapping a node with its neighbor, swapping two nodes, and moving a node to another position.
+//! Crossing variations are computed incrementally from the crossing matrix. Moves that remove crossings
+//! are always accepted, the others with a probability `exp(-delta / temperature)`.
+
+use std::sync::Arc;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    algorithms::{
+        best_heuristic_order, local_search_start_name, solve_free_layers, FreeLayer, ProofStatus,
+        SolveOptions,
+    },
+    cancellation::CancellationToken,
+    crossing_matrix::{crossing_matrix, CrossingMatrix},
+    crossings::count_crossings,
+    events::SolverEvent,
+    graphs::abscissa_graph::AbscissaGraph,
+    precedence::Precedence,
+    solver::{Solver, SolverStats},
+};
+
+/// Maximum distance between the positions of the swap and insertion moves
+const MAX_MOVE_DISTANCE: usize = 32;
+/// Number of random moves sampled to choose the initial temperature
+const TEMPERATURE_SAMPLES: usize = 100;
+/// Ratio to the initial temperature under which the search is frozen
+const FROZEN_RATIO: f64 = 1e-4;
+/// Number of moves between two checks of the cancellation token
+const CANCELLATION_CHECK_INTERVAL: usize = 1 << 10;
+
+/// Cooling schedule of the simulated annealing
+#[derive(Debug, Clone, Copy)]
+pub struct AnnealingSchedule {
+    /// Starting temperature. By default, an average crossing increase is accepted with a probability of 10%
+    pub initial_temperature: Option<f64>,
+    /// Factor applied to the temperature after each step
+    pub cooling_rate: f64,
+    /// Number of moves tried at each temperature (the number of free nodes by default)
+    pub moves_per_temperature: Option<usize>,
+}
+
+impl Default for AnnealingSchedule {
+    fn default() -> Self {
+        AnnealingSchedule {
+            initial_temperature: None,
+            cooling_rate: 0.95,
+            moves_per_temperature: None,
+        }
+    }
+}
+
+impl AnnealingSchedule {
+    /// Check that the schedule freezes in a finite number of steps: a positive and finite initial temperature,
+    /// a cooling rate in `]0, 1[` and at least one move per temperature
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(initial_temperature) = self.initial_temperature {
+            check_initial_temperature(initial_temperature)?;
+        }
+        check_cooling_rate(self.cooling_rate)?;
+        if let Some(moves_per_temperature) = self.moves_per_temperature {
+            check_moves_per_temperature(moves_per_temperature)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that an initial temperature is positive and finite
+pub fn check_initial_temperature(initial_temperature: f64) -> Result<f64, String> {
+    if initial_temperature > 0.0 && initial_temperature.is_finite() {
+        Ok(initial_temperature)
+    } else {
+        Err(format!(
+            "the initial temperature must be positive and finite, got {}",
+            initial_temperature
+        ))
+    }
+}
+
+/// Check that a cooling rate is in `]0, 1[`, so that the temperature decreases
+pub fn check_cooling_rate(cooling_rate: f64) -> Result<f64, String> {
+    if cooling_rate > 0.0 && cooling_rate < 1.0 {
+        Ok(cooling_rate)
+    } else {
+        Err(format!(
+            "the cooling rate must be in ]0, 1[, got {}",
+            cooling_rate
+        ))
+    }
+}
+
+/// Check that at least one move is tried at each temperature
+pub fn check_moves_per_temperature(moves_per_temperature: usize) -> Result<usize, String> {
+    if moves_per_temperature >= 1 {
+        Ok(moves_per_temperature)
+    } else {
+        Err("at least one move must be tried at each temperature".to_string())
+    }
+}
+
+/// Random move in a bottom order
+#[derive(Debug, Clone, Copy)]
+enum Move {
+    /// Swap the nodes at two positions (adjacent or not)
+    Swap(usize, usize),
+    /// Move the node at the first position to the second one
+    Insert(usize, usize),
+}
+
+/// Configuration of the simulated annealing, which improves the order of the free layer(s) of a graph.
+///
+/// Without time limit, the search stops when the temperature is frozen. With a time limit, the temperature
+/// is reset from the best order whenever it is frozen, until the time is up (split between the layers when both are free).
+/// The search also stops when it is cancelled. The best order seen is always returned.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedAnnealingConfig {
+    /// Solver building the starting order, which is then replaced by the best heuristic order if it is better
+    pub start: Option<Arc<dyn Solver>>,
+    /// Seed of the random number generator (random by default)
+    pub seed: Option<u64>,
+    /// Cooling schedule
+    pub schedule: AnnealingSchedule,
+}
+
+/// Result of a simulated annealing run
+#[derive(Debug, Clone)]
+pub struct AnnealingResult {
+    /// Best bottom order seen, from left to right
+    pub order: Vec<usize>,
+    /// Number of crossings removed from the initial order
+    pub removed: u64,
+    /// Number of temperature steps
+    pub steps: u64,
+}
+
+impl Solver for SimulatedAnnealingConfig {
+    fn name(&self) -> &str {
+        "simulated_annealing"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "auto".to_string());
+        vec![
+            ("start", local_search_start_name(&self.start)),
+            ("seed", optional(self.seed.map(|seed| seed.to_string()))),
+            (
+                "initial_temperature",
+                optional(self.schedule.initial_temperature.map(|t| t.to_string())),
+            ),
+            ("cooling_rate", self.schedule.cooling_rate.to_string()),
+            (
+                "moves_per_temperature",
+                optional(self.schedule.moves_per_temperature.map(|m| m.to_string())),
+            ),
+        ]
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        if let Some(start) = &self.start {
+            start.solve(graph, options);
+        }
+
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut rng = StdRng::seed_from_u64(seed);
+        options.observer.notify(SolverEvent::Seed { seed });
+
+        let layer_time_limit = match options.free_layer {
+            FreeLayer::Both => options.time_limit.map(|limit| limit / 2),
+            _ => options.time_limit,
+        };
+        let mut steps = 0;
+
+        solve_free_layers(graph, options.free_layer, |layer_graph| {
+            let cancellation = options.cancellation.with_time_limit(layer_time_limit);
+
+            let precedence = options.bottom_precedence();
+            let order = best_heuristic_order(layer_graph, precedence);
+            layer_graph.set_bottom_order(&order);
+            let initial_crossings = count_crossings(layer_graph);
+
+            let matrix = crossing_matrix(layer_graph);
+            let result = simulated_annealing(
+                &*matrix,
+                order,
+                precedence,
+                &self.schedule,
+                &cancellation,
+                &mut rng,
+            );
+            options.observer.notify(SolverEvent::Finished {
+                iterations: result.steps,
+                crossings: initial_crossings.saturating_sub(result.removed),
+            });
+            steps += result.steps;
+
+            layer_graph.set_bottom_order(&result.order);
+            ProofStatus::Feasible
+        });
+
+        SolverStats {
+            status: ProofStatus::Feasible,
+            iterations: steps,
+        }
+    }
+}
+
+/// Run the simulated annealing from a bottom order.
+/// Returns the best order seen, and the number of crossings it removed from the initial order.
+/// With precedence constraints, the initial order must respect them, and the moves that break them are rejected.
+///
+/// Algorithm
+/// ---------
+/// 1. Sample random moves, and choose the initial temperature from their average crossing increase.
+/// 2. At each temperature, try random moves and accept them with the Metropolis criterion.
+///    Keep a copy of the order whenever it is the best one seen.
+/// 3. Multiply the temperature by the cooling rate, until it is frozen or the search is cancelled.
+///    If the cancellation token has a deadline, the frozen search restarts from the best order until the deadline.
+///
+/// Panics
+/// ------
+/// If the schedule is invalid, see [`AnnealingSchedule::validate`].
+///
+/// Complexity
+/// ----------
+/// * Time: `O(MAX_MOVE_DISTANCE)` crossing matrix queries per move
+/// * Space: `O(V)`
+pub fn simulated_annealing<M: CrossingMatrix + ?Sized, R: Rng>(
+    matrix: &M,
+    mut order: Vec<usize>,
+    precedence: Option<&Precedence>,
+    schedule: &AnnealingSchedule,
+    cancellation: &CancellationToken,
+    rng: &mut R,
+) -> AnnealingResult {
+    if let Err(error) = schedule.validate() {
+        panic!("Invalid annealing schedule: {}", error);
+    }
+    if order.len() < 2 {
+        return AnnealingResult {
+            order,
+            removed: 0,
+            steps: 0,
+        };
+    }
+
+    let initial_temperature = schedule.initial_temperature.unwrap_or_else(|| {
+        let increases: Vec<i128> = (0..TEMPERATURE_SAMPLES)
+            .map(|_| move_delta(matrix, &order, random_move(order.len(), rng)))
+            .filter(|&delta| delta > 0)
+            .collect();
+        let average = increases.iter().sum::<i128>() as f64 / increases.len().max(1) as f64;
+        average.max(1.0) / 10_f64.ln()
+    });
+    let moves_per_temperature = schedule.moves_per_temperature.unwrap_or(order.len());
+
+    // Crossings relative to the initial order
+    let (mut crossings, mut best_crossings) = (0_i128, 0_i128);
+    let mut best_order = order.clone();
+    let mut temperature = initial_temperature;
+    let mut steps = 0;
+
+    'search: loop {
+        for iteration in 0..moves_per_temperature {
+            if iteration % CANCELLATION_CHECK_INTERVAL == 0 && cancellation.is_cancelled() {
+                break 'search;
+            }
+
+            let random_move = random_move(order.len(), rng);
+            if precedence.is_some_and(|precedence| !move_respects(precedence, &order, random_move))
+            {
+                continue;
+            }
+            let delta = move_delta(matrix, &order, random_move);
+            if delta <= 0 || rng.gen::<f64>() < (-delta as f64 / temperature).exp() {
+                apply_move(&mut order, random_move);
+                crossings += delta;
+
+                if crossings < best_crossings {
+                    best_crossings = crossings;
+                    best_order.copy_from_slice(&order);
+                }
+            }
+        }
+        if cancellation.is_cancelled() {
+            break;
+        }
+
+        temperature *= schedule.cooling_rate;
+        steps += 1;
+        if temperature < initial_temperature * FROZEN_RATIO {
+            if cancellation.deadline().is_none() {
+                break;
+            }
+            // Reheat from the best order until the deadline
+            temperature = initial_temperature;
+            order.copy_from_slice(&best_order);
+            crossings = best_crossings;
+        }
+    }
+
+    AnnealingResult {
+        order: best_order,
+        removed: u64::try_from(-best_crossings).unwrap_or(u64::MAX),
+        steps,
+    }
+}
+
+/// Draw a random move in an order of at least 2 nodes
+fn random_move<R: Rng>(size: usize, rng: &mut R) -> Move {
+    let from = rng.gen_range(0..size);
+    let distance = rng.gen_range(1..=MAX_MOVE_DISTANCE.min(size - 1));
+    let to = if rng.gen::<bool>() {
+        (from + distance).min(size - 1)
+    } else {
+        from.saturating_sub(distance)
+    };
+    // Moving towards an end of the order may not move at all
+    let to = if to == from {
+        if from == 0 {
+            1
+        } else {
+            from - 1
+        }
+    } else {
+        to
+    };
+
+    match rng.gen_range(0..3) {
+        0 => Move::Swap(from, if to > from { from + 1 } else { from - 1 }),
+        1 => Move::Swap(from, to),
+        _ => Move::Insert(from, to),
+    }
+}
+
+/// Crossing variation of a move
+fn move_delta<M: CrossingMatrix + ?Sized>(matrix: &M, order: &[usize], random_move: Move) -> i128 {
+    // Crossing variation when `u` goes from before `w` to after `w`
+    let pass = |u: usize, w: usize| matrix.crossings(w, u) as i128 - matrix.crossings(u, w) as i128;
+
+    match random_move {
+        Move::Swap(i, j) => {
+            let (i, j) = (i.min(j), i.max(j));
+            let (u, v) = (order[i], order[j]);
+            order[i + 1..j]
+                .iter()
+                .map(|&w| pass(u, w) - pass(v, w))
+                .sum::<i128>()
+                + pass(u, v)
+        }
+        Move::Insert(from, to) if from < to => order[from + 1..=to]
+            .iter()
+            .map(|&w| pass(order[from], w))
+            .sum(),
+        Move::Insert(from, to) => order[to..from].iter().map(|&w| -pass(order[from], w)).sum(),
+    }
+}
+
+/// Whether a move keeps the forced pairs of an order in their order
+fn move_respects(precedence: &Precedence, order: &[usize], random_move: Move) -> bool {
+    match random_move {
+        Move::Swap(i, j) => {
+            let (i, j) = (i.min(j), i.max(j));
+            let (u, v) = (order[i], order[j]);
+            // `u` goes after the nodes in between and `v`, which goes before them
+            !precedence.is_forced(u, v)
+                && order[i + 1..j]
+                    .iter()
+                    .all(|&w| !precedence.is_forced(u, w) && !precedence.is_forced(w, v))
+        }
+        Move::Insert(from, to) if from < to => order[from + 1..=to]
+            .iter()
+            .all(|&w| !precedence.is_forced(order[from], w)),
+        Move::Insert(from, to) => order[to..from]
+            .iter()
+            .all(|&w| !precedence.is_forced(w, order[from])),
+    }
+}
+
+/// Apply a move to an order
+fn apply_move(order: &mut [usize], random_move: Move) {
+    match random_move {
+        Move::Swap(i, j) => order.swap(i, j),
+        Move::Insert(from, to) if from < to => order[from..=to].rotate_left(1),
+        Move::Insert(from, to) => order[to..=from].rotate_right(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::{
+        algorithms::{branch_and_bound::order_crossings, solve_in_place, Algorithm},
+        crossing_matrix::DenseCrossingMatrix,
+    };
+
+    /// Fast schedule for the tests
+    const SCHEDULE: AnnealingSchedule = AnnealingSchedule {
+        initial_temperature: None,
+        cooling_rate: 0.5,
+        moves_per_temperature: Some(200),
+    };
+
+    #[test]
+    fn move_deltas_match_crossing_counts() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/tiny/website_20.gr").unwrap()).into();
+        let matrix = DenseCrossingMatrix::new(&graph);
+        let mut order = graph.bottom_order();
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..1000 {
+            let random_move = random_move(order.len(), &mut rng);
+            let before = order_crossings(&matrix, &order) as i128;
+            let delta = move_delta(&matrix, &order, random_move);
+            apply_move(&mut order, random_move);
+            assert_eq!(order_crossings(&matrix, &order) as i128, before + delta);
+        }
+    }
+
+    #[test]
+    fn annealing_is_seeded_and_never_worse_than_heuristics() {
+        for filename in [
+            "../datasets/tiny/website_20.gr",
+            "../datasets/tiny/grid_9_shuffled.gr",
+            "../datasets/medium/58.gr",
+        ] {
+            let graph: AbscissaGraph = (&parse_file(filename).unwrap()).into();
+            let config = SimulatedAnnealingConfig {
+                seed: Some(42),
+                schedule: SCHEDULE,
+                ..Default::default()
+            };
+
+            let mut first = graph.clone();
+            solve_in_place(&mut first, &config, &SolveOptions::default());
+            let mut second = graph.clone();
+            solve_in_place(&mut second, &config, &SolveOptions::default());
+            assert_eq!(first.bottom_order(), second.bottom_order(), "{}", filename);
+
+            let mut heuristic = graph.clone();
+            heuristic.set_bottom_order(&best_heuristic_order(&graph, None));
+            assert!(count_crossings(&first) <= count_crossings(&heuristic));
+        }
+    }
+
+    #[test]
+    fn schedules_that_never_freeze_are_rejected() {
+        assert!(SCHEDULE.validate().is_ok());
+        for schedule in [
+            AnnealingSchedule {
+                cooling_rate: 1.0,
+                ..SCHEDULE
+            },
+            AnnealingSchedule {
+                cooling_rate: 1.5,
+                ..SCHEDULE
+            },
+            AnnealingSchedule {
+                initial_temperature: Some(0.0),
+                ..SCHEDULE
+            },
+            AnnealingSchedule {
+                initial_temperature: Some(f64::INFINITY),
+                ..SCHEDULE
+            },
+            AnnealingSchedule {
+                moves_per_temperature: Some(0),
+                ..SCHEDULE
+            },
+        ] {
+            assert!(schedule.validate().is_err(), "{:?}", schedule);
+        }
+    }
+
+    #[test]
+    fn annealing_stops_at_deadline() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/tiny/website_20.gr").unwrap()).into();
+        let options = SolveOptions {
+            time_limit: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+
+        let start_time = Instant::now();
+        let mut solution = graph.clone();
+        solve_in_place(&mut solution, &Algorithm::SimulatedAnnealing, &options);
+        assert!(start_time.elapsed() < Duration::from_secs(5));
+    }
+}
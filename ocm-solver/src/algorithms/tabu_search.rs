@@ -0,0 +1,278 @@
+//! Tabu search over the bottom orders, an alternative to the simulated annealing for large instances.
+//!
+//! At each iteration, the search applies the best insertion move (moving a node to another position), even if it
+//! adds crossings, so that it can leave local minima. The moved node is then tabu for a number of iterations
+//! (the tenure): it cannot be moved again unless it leads to a new best order.
+
+use std::sync::Arc;
+
+use crate::{
+    algorithms::{
+        best_heuristic_order, local_search_start_name, solve_free_layers, FreeLayer, ProofStatus,
+        SolveOptions,
+    },
+    cancellation::CancellationToken,
+    crossing_matrix::{crossing_matrix, CrossingMatrix},
+    crossings::count_crossings,
+    events::{EventObserver, SolverEvent},
+    graphs::abscissa_graph::AbscissaGraph,
+    precedence::Precedence,
+    solver::{Solver, SolverStats},
+};
+
+/// Configuration of the tabu search, which improves the order of the free layer(s) of a graph.
+///
+/// The search stops after the maximum number of iterations, when the time limit is reached (split between the layers
+/// when both are free), or when it is cancelled. The best order seen is always returned.
+#[derive(Debug, Clone)]
+pub struct TabuSearchConfig {
+    /// Solver building the starting order, which is then replaced by the best heuristic order if it is better
+    pub start: Option<Arc<dyn Solver>>,
+    /// Maximum number of iterations (moves) per layer
+    pub max_iterations: u64,
+    /// Number of iterations during which a moved node cannot be moved again
+    /// (an eighth of the number of free nodes by default, between 1 and 16)
+    pub tenure: Option<usize>,
+}
+
+impl Default for TabuSearchConfig {
+    fn default() -> Self {
+        TabuSearchConfig {
+            start: None,
+            max_iterations: 1000,
+            tenure: None,
+        }
+    }
+}
+
+/// Result of a tabu search run
+#[derive(Debug, Clone)]
+pub struct TabuResult {
+    /// Best bottom order seen, from left to right
+    pub order: Vec<usize>,
+    /// Number of crossings removed from the initial order
+    pub removed: u64,
+    /// Number of iterations
+    pub iterations: u64,
+}
+
+impl Solver for TabuSearchConfig {
+    fn name(&self) -> &str {
+        "tabu_search"
+    }
+
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("start", local_search_start_name(&self.start)),
+            ("max_iterations", self.max_iterations.to_string()),
+            (
+                "tenure",
+                self.tenure
+                    .map_or_else(|| "auto".to_string(), |tenure| tenure.to_string()),
+            ),
+        ]
+    }
+
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats {
+        if let Some(start) = &self.start {
+            start.solve(graph, options);
+        }
+
+        let layer_time_limit = match options.free_layer {
+            FreeLayer::Both => options.time_limit.map(|limit| limit / 2),
+            _ => options.time_limit,
+        };
+        let mut iterations = 0;
+
+        solve_free_layers(graph, options.free_layer, |layer_graph| {
+            let cancellation = options.cancellation.with_time_limit(layer_time_limit);
+
+            let precedence = options.bottom_precedence();
+            let order = best_heuristic_order(layer_graph, precedence);
+            layer_graph.set_bottom_order(&order);
+            let initial_crossings = count_crossings(layer_graph);
+
+            let matrix = crossing_matrix(layer_graph);
+            let result = tabu_search(
+                &*matrix,
+                order,
+                initial_crossings,
+                precedence,
+                self,
+                &cancellation,
+                &options.observer,
+            );
+            iterations += result.iterations;
+
+            layer_graph.set_bottom_order(&result.order);
+            ProofStatus::Feasible
+        });
+
+        SolverStats {
+            status: ProofStatus::Feasible,
+            iterations,
+        }
+    }
+}
+
+/// Run the tabu search from a bottom order with `crossings` crossings.
+/// Returns the best order seen, and the number of crossings it removed from the initial order.
+/// The best crossing count after each iteration is sent to the observer.
+/// With precedence constraints, the initial order must respect them, and each node only moves inside its insertion window.
+///
+/// Algorithm
+/// ---------
+/// 1. For each node `v` at position `p`, compute the prefix sums of the crossing deltas `c(w, v) - c(v, w)`
+///    over the nodes `w` of the order. Moving `v` to position `q` then changes the crossing count by
+///    `prefix[q + 1] - prefix[p + 1]` if `q > p`, or `prefix[q] - prefix[p]` if `q < p`.
+/// 2. Apply the best move of a node that is not tabu, or of a tabu node if it gives a new best order (aspiration).
+/// 3. Make the moved node tabu for the tenure, and keep a copy of the order whenever it is the best one seen.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(V^2)` crossing matrix queries per iteration
+/// * Space: `O(V)`
+pub fn tabu_search<M: CrossingMatrix + ?Sized>(
+    matrix: &M,
+    mut order: Vec<usize>,
+    crossings: u64,
+    precedence: Option<&Precedence>,
+    config: &TabuSearchConfig,
+    cancellation: &CancellationToken,
+    observer: &EventObserver,
+) -> TabuResult {
+    let size = order.len();
+    let tenure = config.tenure.unwrap_or((size / 8).clamp(1, 16)) as u64;
+
+    // Crossings relative to the initial order
+    let (mut current, mut best) = (0_i128, 0_i128);
+    let mut best_order = order.clone();
+    // Iteration until which each node is tabu
+    let mut tabu_until = vec![0_u64; size];
+    let mut deltas = vec![0; matrix.size()];
+    let mut prefix = vec![0_i128; size + 1];
+    let mut iteration = 0;
+
+    while iteration < config.max_iterations && size >= 2 && !cancellation.is_cancelled() {
+        // Best admissible move: (crossing delta, position of the node, new position)
+        let mut best_move: Option<(i128, usize, usize)> = None;
+
+        for (position, &v) in order.iter().enumerate() {
+            matrix.crossing_deltas(v, &mut deltas);
+            for (index, &w) in order.iter().enumerate() {
+                prefix[index + 1] = prefix[index].saturating_add(deltas[w]);
+            }
+
+            let tabu = tabu_until[v] > iteration;
+            let window = precedence.map_or(0..=size - 1, |precedence| {
+                precedence.insertion_window(&order, v)
+            });
+            for target in window.filter(|&target| target != position) {
+                let delta = if target > position {
+                    prefix[target + 1] - prefix[position + 1]
+                } else {
+                    prefix[target] - prefix[position]
+                };
+                if tabu && current + delta >= best {
+                    continue;
+                }
+                if best_move.is_none_or(|(best_delta, ..)| delta < best_delta) {
+                    best_move = Some((delta, position, target));
+                }
+            }
+        }
+
+        // Every node is tabu
+        let Some((delta, position, target)) = best_move else {
+            break;
+        };
+
+        iteration += 1;
+        let v = order.remove(position);
+        order.insert(target, v);
+        tabu_until[v] = iteration + tenure;
+        current += delta;
+
+        if current < best {
+            best = current;
+            best_order.copy_from_slice(&order);
+            observer.notify(SolverEvent::Improved {
+                iteration,
+                crossings: (crossings as i128 + best) as u64,
+            });
+        }
+        observer.notify(SolverEvent::Crossings {
+            iteration,
+            crossings: (crossings as i128 + best) as u64,
+        });
+    }
+
+    observer.notify(SolverEvent::Finished {
+        iterations: iteration,
+        crossings: (crossings as i128 + best) as u64,
+    });
+
+    TabuResult {
+        order: best_order,
+        removed: u64::try_from(-best).unwrap_or(u64::MAX),
+        iterations: iteration,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::{
+        algorithms::{exact_dp::exact_dp, solve_in_place},
+        crossing_matrix::DenseCrossingMatrix,
+        crossings::{order_crossings, tests::assert_counts_match},
+    };
+
+    #[test]
+    fn tabu_search_reports_the_crossings_of_its_order() {
+        let config = TabuSearchConfig {
+            max_iterations: 50,
+            ..Default::default()
+        };
+
+        // The crossings of the starting order are those of the returned order plus the removed ones
+        assert_counts_match(|graph, order| {
+            let neighbors = graph.bottom_neighbors();
+            let result = tabu_search(
+                &DenseCrossingMatrix::new(graph),
+                order.to_vec(),
+                order_crossings(&neighbors, order),
+                None,
+                &config,
+                &CancellationToken::new(),
+                &EventObserver::default(),
+            );
+            order_crossings(&neighbors, &result.order) + result.removed
+        });
+    }
+
+    #[test]
+    fn tabu_search_finds_small_optima() {
+        for file in [
+            "website_20",
+            "complete_4_5",
+            "grid_9_shuffled",
+            "cycle_8_shuffled",
+        ] {
+            let graph: AbscissaGraph =
+                (&parse_file(&format!("../datasets/tiny/{}.gr", file)).unwrap()).into();
+            let (_, optimum) = exact_dp(&graph.bottom_neighbors(), 20).unwrap();
+
+            let mut solution = graph.clone();
+            let stats = solve_in_place(
+                &mut solution,
+                &TabuSearchConfig::default(),
+                &SolveOptions::default(),
+            );
+            assert_eq!(count_crossings(&solution), optimum, "{}", file);
+            assert!(stats.iterations <= 1000);
+        }
+    }
+}
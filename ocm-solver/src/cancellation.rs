@@ -0,0 +1,90 @@
+//! Cooperative cancellation of the solvers, for anytime solving within a time budget.
+//!
+//! The iterative algorithms check a shared token regularly, and stop with the best solution found so far
+//! when it is cancelled, either explicitly (for instance from a signal handler) or because its deadline is reached.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Shared cancellation flag, with an optional deadline. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    /// Set when the token is cancelled
+    cancelled: Arc<AtomicBool>,
+    /// Time at which the token is considered cancelled
+    deadline: Option<Instant>,
+}
+
+impl CancellationToken {
+    /// Token that is only cancelled explicitly
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Token sharing the flag of this one, whose deadline is the earliest of its current one and `now + time_limit`
+    pub fn with_time_limit(&self, time_limit: Option<Duration>) -> Self {
+        let deadline = time_limit.map(|limit| Instant::now() + limit);
+        CancellationToken {
+            cancelled: Arc::clone(&self.cancelled),
+            deadline: match (self.deadline, deadline) {
+                (Some(current), Some(deadline)) => Some(current.min(deadline)),
+                (current, deadline) => current.or(deadline),
+            },
+        }
+    }
+
+    /// Flag set when the token is cancelled, to be shared with a signal handler
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.cancelled)
+    }
+
+    /// Time at which the token is considered cancelled, if any
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Cancel this token and all its clones
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the token was cancelled or its deadline is reached
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_the_cancellation() {
+        let token = CancellationToken::new();
+        let limited = token.with_time_limit(Some(Duration::from_secs(3600)));
+        assert!(!limited.is_cancelled());
+
+        token.cancel();
+        assert!(limited.is_cancelled());
+    }
+
+    #[test]
+    fn earliest_deadline_wins() {
+        let token = CancellationToken::new().with_time_limit(Some(Duration::ZERO));
+        assert!(token.is_cancelled());
+        assert!(token
+            .with_time_limit(Some(Duration::from_secs(3600)))
+            .is_cancelled());
+        assert!(!CancellationToken::new()
+            .with_time_limit(None)
+            .is_cancelled());
+    }
+}
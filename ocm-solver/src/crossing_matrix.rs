@@ -0,0 +1,368 @@
+//! Pairwise crossing counts between bottom nodes, for a fixed top layer order.
+//!
+//! `c(u, v)` is the number of crossings between the edges of the bottom nodes `u` and `v`
+//! when `u` is placed to the left of `v`. It does not depend on the position of the other bottom nodes,
+//! so the crossing count of a bottom order is the sum of `c(u, v)` over all pairs where `u` is left of `v`.
+//!
+//! Two implementations are available:
+//! - [`DenseCrossingMatrix`] precomputes all pairs, for small graphs.
+//! - [`SparseCrossingMatrix`] computes pairs on demand from the node neighborhoods, for large graphs.
+
+use ocm_parser::bipartite_graph::BipartiteGraph;
+
+use crate::graphs::abscissa_graph::{AbscissaGraph, WeightedRank};
+
+/// Largest number of bottom nodes for which [`crossing_matrix`] precomputes all pairs (32 MB of counts)
+pub const DENSE_MATRIX_MAX_SIZE: usize = 2048;
+
+/// Pairwise crossing counts between bottom nodes
+pub trait CrossingMatrix {
+    /// Number of bottom nodes
+    fn size(&self) -> usize;
+
+    /// Crossings between the edges of `u` and `v` when `u` is placed to the left of `v`
+    fn crossings(&self, u: usize, v: usize) -> u64;
+
+    /// Fill `deltas[w]` with `c(w, v) - c(v, w)` for every node `w`, i.e. the change of the crossing count
+    /// when `v` moves from before `w` to after `w`. `deltas[v]` is set to 0.
+    fn crossing_deltas(&self, v: usize, deltas: &mut [i128]) {
+        for (w, delta) in deltas.iter_mut().enumerate() {
+            *delta = if w == v {
+                0
+            } else {
+                self.crossings(w, v) as i128 - self.crossings(v, w) as i128
+            };
+        }
+    }
+}
+
+/// Build the most appropriate crossing matrix for the size of the graph
+pub fn crossing_matrix(graph: &AbscissaGraph) -> Box<dyn CrossingMatrix + Sync> {
+    if graph.bottom_nodes_abscissas.len() <= DENSE_MATRIX_MAX_SIZE {
+        Box::new(DenseCrossingMatrix::new(graph))
+    } else {
+        Box::new(SparseCrossingMatrix::new(graph))
+    }
+}
+
+/// Compute `(c(u, v), c(v, u))` from the weighted top neighbor ranks of `u` and `v`, sorted by rank.
+/// Two crossing edges count as the product of their weights, and the counts saturate at `u64::MAX`.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(deg(u) + deg(v))`
+/// * Space: `O(1)`
+pub fn pair_crossings(u_neighbors: &[WeightedRank], v_neighbors: &[WeightedRank]) -> (u64, u64) {
+    let (Some(u_first), Some(u_last), Some(v_first), Some(v_last)) = (
+        u_neighbors.first(),
+        u_neighbors.last(),
+        v_neighbors.first(),
+        v_neighbors.last(),
+    ) else {
+        return (0, 0); // Isolated nodes never cross
+    };
+
+    // Strictly separated neighborhoods: every pair of edges crosses in one order only
+    let v_weight = total_weight(v_neighbors);
+    if u_last.0 < v_first.0 {
+        return (0, total_weight(u_neighbors).saturating_mul(v_weight));
+    }
+    if v_last.0 < u_first.0 {
+        return (total_weight(u_neighbors).saturating_mul(v_weight), 0);
+    }
+
+    // c(u, v) counts the pairs (a, b) with a in N(u), b in N(v) and a > b, weighted by their edge weights.
+    // For each a, sum the weights of the elements of N(v) strictly smaller (resp. strictly greater) than a.
+    let (mut u_first_crossings, mut v_first_crossings) = (0_u64, 0_u64);
+    let (mut smaller, mut smaller_or_equal) = (0, 0);
+    let (mut smaller_weight, mut smaller_or_equal_weight) = (0_u64, 0_u64);
+    for &(a, a_weight) in u_neighbors {
+        while smaller < v_neighbors.len() && v_neighbors[smaller].0 < a {
+            smaller_weight = smaller_weight.saturating_add(v_neighbors[smaller].1);
+            smaller += 1;
+        }
+        while smaller_or_equal < v_neighbors.len() && v_neighbors[smaller_or_equal].0 <= a {
+            smaller_or_equal_weight =
+                smaller_or_equal_weight.saturating_add(v_neighbors[smaller_or_equal].1);
+            smaller_or_equal += 1;
+        }
+        u_first_crossings =
+            u_first_crossings.saturating_add(a_weight.saturating_mul(smaller_weight));
+        v_first_crossings = v_first_crossings
+            .saturating_add(a_weight.saturating_mul(v_weight - smaller_or_equal_weight));
+    }
+
+    (u_first_crossings, v_first_crossings)
+}
+
+/// Sum of the edge weights of a node, saturating at `u64::MAX`
+fn total_weight(neighbors: &[WeightedRank]) -> u64 {
+    neighbors
+        .iter()
+        .fold(0_u64, |total, (_, weight)| total.saturating_add(*weight))
+}
+
+/// Crossing matrix with all pairs precomputed.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(V * E)` to build, `O(1)` per query
+/// * Space: `O(V^2)`
+#[derive(Debug, Clone)]
+pub struct DenseCrossingMatrix {
+    size: usize,
+    values: Vec<u64>,
+}
+
+impl DenseCrossingMatrix {
+    /// Compute all pairwise crossing counts of the bottom nodes of a graph
+    pub fn new(graph: &AbscissaGraph) -> Self {
+        Self::from_neighbors(&graph.bottom_neighbors())
+    }
+
+    /// Compute all pairwise crossing counts from the weighted top neighbor ranks of each bottom node
+    pub fn from_neighbors(neighbors: &[Vec<WeightedRank>]) -> Self {
+        let size = neighbors.len();
+        let mut values = vec![0; size * size];
+
+        for u in 0..size {
+            for v in u + 1..size {
+                let (uv, vu) = pair_crossings(&neighbors[u], &neighbors[v]);
+                values[u * size + v] = uv;
+                values[v * size + u] = vu;
+            }
+        }
+
+        DenseCrossingMatrix { size, values }
+    }
+}
+
+impl CrossingMatrix for DenseCrossingMatrix {
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn crossings(&self, u: usize, v: usize) -> u64 {
+        self.values[u * self.size + v]
+    }
+}
+
+impl From<&BipartiteGraph> for DenseCrossingMatrix {
+    fn from(graph: &BipartiteGraph) -> Self {
+        Self::new(&graph.into())
+    }
+}
+
+/// Crossing matrix computing pairs on demand from the node neighborhoods.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(E * log(E))` to build, `O(deg(u) + deg(v))` per query
+/// * Space: `O(V + E)`
+#[derive(Debug, Clone)]
+pub struct SparseCrossingMatrix {
+    neighbors: Vec<Vec<WeightedRank>>,
+    /// Bottom nodes and edge weights adjacent to each top rank
+    top_neighbors: Vec<Vec<(usize, u64)>>,
+    /// Sum of the edge weights of each bottom node
+    total_weights: Vec<u64>,
+}
+
+impl SparseCrossingMatrix {
+    /// Prepare the neighborhoods of the bottom nodes of a graph
+    pub fn new(graph: &AbscissaGraph) -> Self {
+        Self::from_neighbors(graph.bottom_neighbors())
+    }
+
+    /// Use the weighted top neighbor ranks of each bottom node
+    pub fn from_neighbors(neighbors: Vec<Vec<WeightedRank>>) -> Self {
+        let top_count = neighbors
+            .iter()
+            .filter_map(|neighbors| neighbors.last())
+            .map(|&(rank, _)| rank as usize + 1)
+            .max()
+            .unwrap_or(0);
+        let mut top_neighbors = vec![vec![]; top_count];
+        for (u, u_neighbors) in neighbors.iter().enumerate() {
+            for &(rank, weight) in u_neighbors {
+                top_neighbors[rank as usize].push((u, weight));
+            }
+        }
+        let total_weights = neighbors
+            .iter()
+            .map(|neighbors| total_weight(neighbors))
+            .collect();
+
+        SparseCrossingMatrix {
+            neighbors,
+            top_neighbors,
+            total_weights,
+        }
+    }
+
+    /// Weighted top neighbor ranks of a bottom node, sorted by rank
+    pub fn neighbors(&self, u: usize) -> &[WeightedRank] {
+        &self.neighbors[u]
+    }
+
+    /// List the pairs `(u, v)` with `u < v` whose neighborhoods overlap, i.e. the only pairs for which
+    /// both `c(u, v)` and `c(v, u)` can be non-zero. All other pairs have a forced order.
+    ///
+    /// Complexity
+    /// ----------
+    /// * Time: `O(V * log(V) + P)` where `P` is the number of overlapping pairs
+    pub fn overlapping_pairs(&self) -> Vec<(usize, usize)> {
+        // Sort the non isolated nodes by the left end of their neighborhood interval
+        let mut nodes: Vec<usize> = (0..self.neighbors.len())
+            .filter(|&u| !self.neighbors[u].is_empty())
+            .collect();
+        nodes.sort_by_key(|&u| self.neighbors[u][0].0);
+
+        let mut pairs = Vec::new();
+        for (i, &u) in nodes.iter().enumerate() {
+            let u_last = self.neighbors[u].last().unwrap().0;
+
+            // Following nodes overlap as long as their interval starts strictly before the end of u
+            for &v in nodes[i + 1..]
+                .iter()
+                .take_while(|&&v| self.neighbors[v][0].0 < u_last)
+            {
+                pairs.push((u.min(v), u.max(v)));
+            }
+        }
+
+        pairs
+    }
+}
+
+impl CrossingMatrix for SparseCrossingMatrix {
+    fn size(&self) -> usize {
+        self.neighbors.len()
+    }
+
+    fn crossings(&self, u: usize, v: usize) -> u64 {
+        pair_crossings(&self.neighbors[u], &self.neighbors[v]).0
+    }
+
+    /// Compute all the deltas of `v` in one sweep over the top ranks spanned by its neighborhood.
+    ///
+    /// An edge of `w` to the top rank `a` crosses the edges of `v` to the smaller ranks when `w` is left of `v`,
+    /// and the edges of `v` to the greater ranks otherwise, so it adds `weight * (less(a) - greater(a))` to the delta,
+    /// where `less(a)` and `greater(a)` are the weights of the edges of `v` to the smaller and greater ranks.
+    /// This factor is `-W(v)` before the first neighbor of `v` and `W(v)` after its last one: only the edges
+    /// within the neighborhood interval of `v` need to be swept, and the others are counted from the node totals.
+    ///
+    /// Complexity
+    /// ----------
+    /// * Time: `O(V + E_v)`, where `E_v` is the number of edges of the nodes overlapping the neighborhood of `v`
+    /// * Space: `O(1)`
+    fn crossing_deltas(&self, v: usize, deltas: &mut [i128]) {
+        let v_neighbors = &self.neighbors[v];
+        let (Some(&(v_first, _)), Some(&(v_last, _))) = (v_neighbors.first(), v_neighbors.last())
+        else {
+            deltas.fill(0); // Isolated nodes never cross
+            return;
+        };
+        let v_weight = self.total_weights[v] as i128;
+
+        // Edges before the interval count as -W(v), and edges after it as W(v)
+        for (w, delta) in deltas.iter_mut().enumerate() {
+            let w_neighbors = &self.neighbors[w];
+            let w_weight = self.total_weights[w] as i128;
+            let after_weight = match (w_neighbors.first(), w_neighbors.last()) {
+                (Some(&(w_first, _)), _) if w_first > v_last => w_weight,
+                (_, Some(&(w_last, _))) if w_last > v_last => {
+                    let after = w_neighbors.partition_point(|&(rank, _)| rank <= v_last);
+                    total_weight(&w_neighbors[after..]) as i128
+                }
+                _ => 0,
+            };
+            *delta = v_weight
+                .saturating_mul(2 * after_weight)
+                .saturating_sub(v_weight.saturating_mul(w_weight));
+        }
+
+        // Edges within the interval count as less(a) - greater(a) = 2 * less(a) + equal(a) - W(v)
+        let mut less = 0_i128;
+        let mut next = 0;
+        for rank in v_first..=v_last {
+            let mut equal = 0_i128;
+            if v_neighbors[next].0 == rank {
+                equal = v_neighbors[next].1 as i128;
+                next += 1;
+            }
+            let factor = 2 * less + equal;
+            for &(w, weight) in &self.top_neighbors[rank as usize] {
+                deltas[w] = deltas[w].saturating_add((weight as i128).saturating_mul(factor));
+            }
+            less += equal;
+        }
+        deltas[v] = 0;
+    }
+}
+
+impl From<&BipartiteGraph> for SparseCrossingMatrix {
+    fn from(graph: &BipartiteGraph) -> Self {
+        Self::new(&graph.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::crossings::count_crossings;
+
+    #[test]
+    fn pair_crossings_counts() {
+        assert_eq!(pair_crossings(&[(0, 1), (2, 1)], &[(1, 1)]), (1, 1));
+        assert_eq!(pair_crossings(&[(0, 1), (1, 1)], &[(1, 1), (2, 1)]), (0, 3));
+        assert_eq!(pair_crossings(&[(1, 2)], &[(0, 1), (1, 1), (2, 1)]), (2, 2));
+        assert_eq!(pair_crossings(&[(3, 1), (4, 1)], &[(0, 1), (1, 1)]), (4, 0));
+        assert_eq!(pair_crossings(&[], &[(0, 1), (1, 1)]), (0, 0));
+
+        // Weighted edges cross like parallel edges, without overflowing
+        assert_eq!(pair_crossings(&[(0, 3), (2, 1)], &[(1, 5)]), (5, 15));
+        assert_eq!(pair_crossings(&[(1, u64::MAX)], &[(0, 2)]), (u64::MAX, 0));
+    }
+
+    #[test]
+    fn matrices_sum_to_crossing_count() {
+        for filename in [
+            "../datasets/tiny/cycle_8_shuffled.gr",
+            "../datasets/tiny/website_20.gr",
+            "../datasets/medium/58.gr",
+        ] {
+            let graph = parse_file(filename).unwrap();
+            let dense = DenseCrossingMatrix::from(&graph);
+            let sparse = SparseCrossingMatrix::from(&graph);
+            let mut overlapping = sparse.overlapping_pairs();
+            overlapping.sort_unstable();
+
+            // In the natural order, u is left of v for all u < v
+            let mut crossings = 0;
+            for u in 0..dense.size() {
+                for v in u + 1..dense.size() {
+                    assert_eq!(dense.crossings(u, v), sparse.crossings(u, v));
+                    assert_eq!(dense.crossings(v, u), sparse.crossings(v, u));
+                    if dense.crossings(u, v) > 0 && dense.crossings(v, u) > 0 {
+                        assert!(overlapping.binary_search(&(u, v)).is_ok());
+                    }
+                    crossings += dense.crossings(u, v);
+                }
+            }
+
+            assert_eq!(crossings, count_crossings(&graph), "{}", filename);
+
+            // The sparse sweep computes the same deltas as the pair queries
+            let (mut dense_deltas, mut sparse_deltas) =
+                (vec![0; dense.size()], vec![0; dense.size()]);
+            for v in 0..dense.size() {
+                dense.crossing_deltas(v, &mut dense_deltas);
+                sparse.crossing_deltas(v, &mut sparse_deltas);
+                assert_eq!(dense_deltas, sparse_deltas, "{}: node {}", filename, v);
+            }
+        }
+    }
+}
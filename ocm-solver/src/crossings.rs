@@ -3,12 +3,16 @@
 //!
 //! All HashMaps and HashSets use the [`ahash`](crate::ahash) for better performance (cryptographic security is not needed).
 
-use crate::algo_utils::edges_min_index_sort;
+use crate::{
+    algo_utils::{edges_lexicographic_sort, edges_min_index_sort, FenwickTree},
+    graphs::abscissa_graph::WeightedRank,
+};
 
-use ahash::AHashSet;
+use ahash::AHashMap;
 use ocm_parser::graph_base::{Edge, OrderedGraph};
 
 /// Count the crossings in a graph using the line sweep algorithm.
+/// Two crossing edges count as the product of their weights, and the count saturates at `u64::MAX`.
 ///
 /// Algorithm
 /// ---------
@@ -24,24 +28,27 @@ use ocm_parser::graph_base::{Edge, OrderedGraph};
 /// * Time: `O((E + V) * E) worst case. Can go down to `O((E + V) * log(E))` if the graph is sparse.
 /// * Space: `O(E)`
 ///
+/// Prefer [`accumulator_tree_crossings`], which is much faster. This implementation is kept for cross-checking.
+///
 pub fn line_sweep_crossings<T: OrderedGraph>(graph: &T) -> u64 {
     // Sort the edges using their minimum index in order to swipe through them in appearance order.
     // Time: O(E * log(E))
     // Space: O(E)
-    let mut edges = graph.get_ordered_edges();
+    let mut edges = graph.get_ordered_weighted_edges();
     edges_min_index_sort(&mut edges);
 
-    // Store currently active edges
+    // Store currently active edges, along with their multiplicity (sum of the weights of parallel edges)
     // Space: O(E)
-    let mut active_edges: AHashSet<Edge> = AHashSet::new();
+    let mut active_edges: AHashMap<Edge, u64> = AHashMap::new();
     let mut line_position = 0_u64; // Current index swept by the line
     let mut crossings = 0_u64; // Total number of crossings found
     let mut line_position_changed; // Boolean flag
 
     // Iterate through the edges in appearance order
     // Time: O((E + V) * E) ? Not sure...
-    for edge in &edges {
-        let new_line_position = edge.0.min(edge.1); // Update the line position to the appearance index of the new edge
+    for &(top, bottom, weight) in &edges {
+        let edge = (top, bottom);
+        let new_line_position = top.min(bottom); // Update the line position to the appearance index of the new edge
         line_position_changed = false;
         if new_line_position != line_position {
             line_position = new_line_position;
@@ -49,10 +56,11 @@ pub fn line_sweep_crossings<T: OrderedGraph>(graph: &T) -> u64 {
         }
 
         // 1. Compare with active edges
-        crossings += scan_edges_for_crossings(&active_edges, edge);
+        crossings = crossings
+            .saturating_add(weight.saturating_mul(scan_edges_for_crossings(&active_edges, &edge)));
 
         // 2. Add to active edges
-        active_edges.insert(*edge);
+        *active_edges.entry(edge).or_insert(0) += weight;
 
         // 3. Remove dead edges (ie: their max index is less or equal to the current line position)
         // Only do this if the line position has changed. We forget cleaning (n,n) vertical edges,
@@ -65,22 +73,106 @@ pub fn line_sweep_crossings<T: OrderedGraph>(graph: &T) -> u64 {
     crossings
 }
 
+/// Count the crossings in a graph with the default crossing counter, see [`accumulator_tree_crossings`].
+pub fn count_crossings<T: OrderedGraph>(graph: &T) -> u64 {
+    accumulator_tree_crossings(graph)
+}
+
+/// Count the crossings in a graph by counting inversions with an accumulator tree (Fenwick tree).
+/// Two crossing edges count as the product of their weights, and the count saturates at `u64::MAX`.
+///
+/// Algorithm
+/// ---------
+/// 1. Sort edges in lexicographic order (top index, then bottom index).
+/// 2. Two edges `(t1, b1)` and `(t2, b2)` with `t1 < t2` cross if and only if `b1 > b2`.
+///    Iterate through the sorted edges, and for each edge sum the weights of the previous edges with a strictly
+///    greater bottom index using an accumulator tree indexed by bottom index, then insert its weight in the tree.
+///    Edges that share their top node are sorted by bottom index, so they are never counted as crossing.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(E * log(E) + E * log(V))`
+/// * Space: `O(E + V)`
+///
+pub fn accumulator_tree_crossings<T: OrderedGraph>(graph: &T) -> u64 {
+    let mut edges = graph.get_ordered_weighted_edges();
+    edges_lexicographic_sort(&mut edges);
+
+    let bottom_count = edges
+        .iter()
+        .map(|(_, bottom, _)| bottom + 1)
+        .max()
+        .unwrap_or(0);
+    let mut tree = FenwickTree::new(bottom_count as usize);
+    let mut inserted = 0_u64; // Total weight of the previous edges
+    let mut crossings = 0_u64;
+
+    for &(_, bottom, weight) in &edges {
+        // Previous edges with a bottom index strictly greater than the current one
+        crossings = crossings
+            .saturating_add(weight.saturating_mul(inserted - tree.prefix_sum(bottom as usize)));
+        tree.add(bottom as usize, weight);
+        inserted += weight;
+    }
+
+    crossings
+}
+
+/// Count the crossings of a bottom order from the weighted top neighbor ranks of each bottom node,
+/// without building the reordered graph (see [`AbscissaGraph::bottom_neighbors`](crate::graphs::abscissa_graph::AbscissaGraph::bottom_neighbors)).
+///
+/// Algorithm
+/// ---------
+/// Iterate through the bottom nodes from left to right. Each edge of a node crosses the edges of the previous nodes
+/// that end on a strictly greater top rank, whose weights are summed with an accumulator tree indexed by top rank.
+/// The edges of a node are inserted in the tree after all of them are counted, so that they never cross each other.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(E * log(V))`
+/// * Space: `O(V)`
+///
+pub fn order_crossings(neighbors: &[Vec<WeightedRank>], order: &[usize]) -> u64 {
+    let top_count = neighbors
+        .iter()
+        .filter_map(|ranks| ranks.last())
+        .map(|(rank, _)| rank + 1)
+        .max()
+        .unwrap_or(0);
+    let mut tree = FenwickTree::new(top_count as usize);
+    let mut inserted = 0_u64;
+    let mut crossings = 0_u64;
+
+    for &u in order {
+        for &(rank, weight) in &neighbors[u] {
+            crossings = crossings
+                .saturating_add(weight.saturating_mul(inserted - tree.prefix_sum(rank as usize)));
+        }
+        for &(rank, weight) in &neighbors[u] {
+            tree.add(rank as usize, weight);
+            inserted += weight;
+        }
+    }
+
+    crossings
+}
+
 // Helper functions
 
 /// Given a vertical line position, remove all dead edges from a set of active edges.
 /// A dead edge is an edge whose maximum index is less or equal to the line position.
-fn remove_dead_edges(active_edges: &mut AHashSet<Edge>, line_position: u64) {
-    active_edges.retain(|&(start, end)| start > line_position || end > line_position);
+fn remove_dead_edges(active_edges: &mut AHashMap<Edge, u64>, line_position: u64) {
+    active_edges.retain(|&(start, end), _| start > line_position || end > line_position);
 }
 
 /// Given a set of active edges and a new edge, scan the active edges for crossings with the new edge.
-fn scan_edges_for_crossings(active_edges: &AHashSet<Edge>, edge: &Edge) -> u64 {
+fn scan_edges_for_crossings(active_edges: &AHashMap<Edge, u64>, edge: &Edge) -> u64 {
     let mut crossings = 0_u64;
 
-    for (start, end) in active_edges {
+    for ((start, end), multiplicity) in active_edges {
         // There is a crossing if there is an inversion in the product of the direction differences.
         if (*start as i64 - edge.0 as i64) * (*end as i64 - edge.1 as i64) < 0 {
-            crossings += 1;
+            crossings += multiplicity;
         }
     }
 
@@ -88,8 +180,8 @@ fn scan_edges_for_crossings(active_edges: &AHashSet<Edge>, edge: &Edge) -> u64 {
 }
 
 #[cfg(test)]
-mod tests {
-    use ocm_parser::parse_file;
+pub(crate) mod tests {
+    use ocm_parser::{bipartite_graph::BipartiteGraph, parse_file};
     use rayon::prelude::*;
     use std::{
         fs::File,
@@ -101,8 +193,155 @@ mod tests {
     };
     use walkdir::WalkDir;
 
+    use super::*;
+    use crate::graphs::abscissa_graph::AbscissaGraph;
+
+    /// Check a crossing counter of bottom orders against [`accumulator_tree_crossings`], on the tiny graphs
+    /// and a medium one. `count(graph, order)` counts the crossings of `graph` with its bottom nodes in `order`,
+    /// which is checked for the current order of each graph, and the reversed order.
+    pub(crate) fn assert_counts_match(count: impl Fn(&AbscissaGraph, &[usize]) -> u64) {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .chain(WalkDir::new("../datasets/medium/68.gr"))
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let graph: AbscissaGraph = (&parse_file(file.path().to_str().unwrap()).unwrap()).into();
+
+            let order = graph.bottom_order();
+            let reversed_order: Vec<usize> = order.iter().rev().copied().collect();
+            for order in [order, reversed_order] {
+                let mut reordered = graph.clone();
+                reordered.set_bottom_order(&order);
+                assert_eq!(
+                    count(&graph, &order),
+                    accumulator_tree_crossings(&reordered),
+                    "{}",
+                    file.path().display()
+                );
+            }
+        }
+    }
+
+    /// Reference crossing counter comparing every pair of edges. Time: `O(E^2)`
+    fn pairwise_crossings(graph: &BipartiteGraph) -> u64 {
+        let edges = graph.get_ordered_weighted_edges();
+        let mut crossings = 0;
+        for (i, (top1, bottom1, weight1)) in edges.iter().enumerate() {
+            for (top2, bottom2, weight2) in &edges[i + 1..] {
+                if (top1 < top2 && bottom1 > bottom2) || (top1 > top2 && bottom1 < bottom2) {
+                    crossings += weight1 * weight2;
+                }
+            }
+        }
+        crossings
+    }
+
+    #[test]
+    fn accumulator_tree_matches_pairwise_count() {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let graph = parse_file(file.path().to_str().unwrap()).unwrap();
+            assert_eq!(
+                accumulator_tree_crossings(&graph),
+                pairwise_crossings(&graph),
+                "{}",
+                file.path().display()
+            );
+        }
+    }
+
+    #[test]
+    fn accumulator_tree_matches_line_sweep() {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .chain(WalkDir::new("../datasets/medium"))
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let graph = parse_file(file.path().to_str().unwrap()).unwrap();
+            if graph.edges.len() > 10_000 {
+                continue; // The line sweep is too slow on the largest medium graphs
+            }
+            assert_eq!(
+                accumulator_tree_crossings(&graph),
+                line_sweep_crossings(&graph),
+                "{}",
+                file.path().display()
+            );
+        }
+    }
+
+    #[test]
+    fn order_crossings_matches_accumulator_tree() {
+        assert_counts_match(|graph, order| order_crossings(&graph.bottom_neighbors(), order));
+    }
+
+    #[test]
+    fn weighted_edges_cross_like_parallel_edges() {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let mut weighted = parse_file(file.path().to_str().unwrap()).unwrap();
+            weighted.weights = (0..weighted.edges.len() as u64)
+                .map(|index| index % 3 + 1)
+                .collect();
+
+            // Replace each edge of weight w by w parallel edges
+            let mut parallel = weighted.clone();
+            parallel.weights.clear();
+            parallel.edges = (0..weighted.edges.len())
+                .flat_map(|index| {
+                    std::iter::repeat_n(weighted.edges[index], weighted.weights[index] as usize)
+                })
+                .collect();
+            let expected = pairwise_crossings(&parallel);
+
+            assert_eq!(pairwise_crossings(&weighted), expected);
+            assert_eq!(accumulator_tree_crossings(&weighted), expected);
+            assert_eq!(line_sweep_crossings(&weighted), expected);
+
+            let abscissa_graph: AbscissaGraph = (&weighted).into();
+            let neighbors = abscissa_graph.bottom_neighbors();
+            assert_eq!(
+                order_crossings(&neighbors, &abscissa_graph.bottom_order()),
+                expected
+            );
+
+            parallel.merge_parallel_edges();
+            assert_eq!(
+                accumulator_tree_crossings(&parallel),
+                expected,
+                "{}",
+                file.path().display()
+            );
+        }
+    }
+
     #[test]
     fn benchmark_crossings() {
+        run_crossings_benchmark(line_sweep_crossings, "../crossings_benchmark.csv");
+    }
+
+    #[test]
+    fn benchmark_accumulator_tree_crossings() {
+        run_crossings_benchmark(
+            accumulator_tree_crossings,
+            "../accumulator_tree_crossings_benchmark.csv",
+        );
+    }
+
+    /// Benchmark a crossing counter over all datasets, and progressively write the results to a CSV file
+    fn run_crossings_benchmark(counter: fn(&BipartiteGraph) -> u64, output: &str) {
         println!("Benchmarking crossings...");
 
         let files = WalkDir::new("../datasets")
@@ -116,15 +355,15 @@ mod tests {
         let processed_count = Arc::new(AtomicUsize::new(0));
 
         // Progressively write the results to a file
-        let file = File::create("../crossings_benchmark.csv").unwrap();
+        let file = File::create(output).unwrap();
         let writer = Arc::new(Mutex::new(BufWriter::new(file)));
 
         files.par_iter().for_each(|filename| {
-            let graph = parse_file(filename);
+            let graph = parse_file(filename).unwrap();
 
             // Time the crossings computation
             let start_time = std::time::Instant::now();
-            crate::crossings::line_sweep_crossings(&graph);
+            counter(&graph);
             let elapsed_time = start_time.elapsed();
 
             // Write the results to the file
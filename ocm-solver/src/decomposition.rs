@@ -0,0 +1,279 @@
+//! Decomposition of the one-sided problem into independent components (the bottom layer is the free one).
+//!
+//! Two bottom nodes whose neighborhood intervals on the fixed layer do not overlap (or only share an endpoint)
+//! can be ordered without crossings between them. Sweeping the intervals from left to right splits the bottom
+//! nodes into components that can be solved independently, and concatenated from left to right.
+//! This separates the connected components of the graph, and also splits them further when possible.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use rayon::prelude::*;
+
+use crate::{
+    algorithms::{solve_in_place, FreeLayer, ProofStatus, SolveOptions},
+    crossings::count_crossings,
+    events::{EventObserver, SolverEvent},
+    graphs::abscissa_graph::{AbscissaGraph, WeightedRank},
+    solver::{Solver, SolverStats},
+};
+
+/// Statistics of a solved component
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentStats {
+    pub bottom_node_count: usize,
+    pub top_node_count: usize,
+    pub edge_count: usize,
+    pub initial_crossings: u64,
+    pub final_crossings: u64,
+    pub elapsed_time: Duration,
+    pub status: ProofStatus,
+    pub iterations: u64,
+}
+
+/// Independent components of the bottom layer of a graph
+#[derive(Debug, Clone)]
+pub struct Decomposition {
+    /// Weighted top neighbor ranks of each bottom node
+    neighbors: Vec<Vec<WeightedRank>>,
+    /// Bottom nodes of each component, components from left to right
+    components: Vec<Vec<usize>>,
+    /// Isolated bottom nodes
+    isolated: Vec<usize>,
+}
+
+impl Decomposition {
+    /// Decompose the bottom layer of a graph
+    ///
+    /// Complexity
+    /// ----------
+    /// * Time: `O(V * log(V) + E * log(E))`
+    /// * Space: `O(V + E)`
+    pub fn new(graph: &AbscissaGraph) -> Self {
+        let neighbors = graph.bottom_neighbors();
+        let components = interval_blocks(&neighbors);
+        let isolated = (0..neighbors.len())
+            .filter(|&u| neighbors[u].is_empty())
+            .collect();
+
+        Decomposition {
+            neighbors,
+            components,
+            isolated,
+        }
+    }
+
+    /// Bottom nodes of each component, from left to right
+    pub fn components(&self) -> &[Vec<usize>] {
+        &self.components
+    }
+
+    /// Build the graph of a component. Its bottom nodes are those of the component, in the same order as
+    /// [`Decomposition::components`], and keep their current relative order.
+    pub fn component_graph(&self, graph: &AbscissaGraph, component: usize) -> AbscissaGraph {
+        bottom_subgraph(
+            self.components[component]
+                .iter()
+                .map(|&u| (&self.neighbors[u][..], graph.bottom_nodes_abscissas[u])),
+        )
+    }
+
+    /// Concatenate the solutions of the components into a full bottom order, with the isolated nodes at the end.
+    /// `component_orders` contains the local bottom order of each component graph, see [`AbscissaGraph::bottom_order`].
+    pub fn stitch(&self, component_orders: &[Vec<usize>]) -> Vec<usize> {
+        self.components
+            .iter()
+            .zip(component_orders)
+            .flat_map(|(component, order)| order.iter().map(|&local| component[local]))
+            .chain(self.isolated.iter().copied())
+            .collect()
+    }
+}
+
+/// Split the bottom nodes with neighbors into blocks whose neighborhood intervals do not overlap,
+/// from the weighted top neighbor ranks of each bottom node.
+/// Blocks are sorted from left to right, and the nodes of each block by index.
+///
+/// Algorithm
+/// ---------
+/// 1. Sort the nodes by the start of their neighborhood interval.
+/// 2. Start a new block when a node starts at or after the end of all the previous intervals.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(V * log(V))`
+/// * Space: `O(V)`
+pub fn interval_blocks(neighbors: &[Vec<WeightedRank>]) -> Vec<Vec<usize>> {
+    let interval = |u: usize| (neighbors[u][0].0, neighbors[u].last().unwrap().0);
+    let mut nodes: Vec<usize> = (0..neighbors.len())
+        .filter(|&u| !neighbors[u].is_empty())
+        .collect();
+    nodes.sort_by_key(|&u| interval(u));
+
+    let mut blocks: Vec<Vec<usize>> = Vec::new();
+    let mut block_end = 0;
+    for u in nodes {
+        let (start, end) = interval(u);
+        match blocks.last_mut() {
+            Some(block) if start < block_end => block.push(u),
+            _ => blocks.push(vec![u]),
+        }
+        block_end = block_end.max(end);
+    }
+    blocks.iter_mut().for_each(|block| block.sort_unstable());
+
+    blocks
+}
+
+/// Build a graph from the weighted top neighbor ranks and the abscissa of each of its bottom nodes.
+/// Only the top nodes adjacent to these bottom nodes are kept, in the same order.
+pub(crate) fn bottom_subgraph<'a, I>(bottom_nodes: I) -> AbscissaGraph
+where
+    I: Iterator<Item = (&'a [WeightedRank], f64)>,
+{
+    let mut top_local = ahash::AHashMap::new();
+    let mut graph = AbscissaGraph::new();
+
+    for (local, (neighbors, abscissa)) in bottom_nodes.enumerate() {
+        for &(rank, weight) in neighbors {
+            let top_index = *top_local.entry(rank).or_insert_with(|| {
+                graph.top_nodes_abscissas.push(rank as f64);
+                graph.top_nodes_abscissas.len() - 1
+            });
+            graph.edges.push((top_index as u64, local as u64));
+            graph.weights.push(weight);
+        }
+        graph.bottom_nodes_abscissas.push(abscissa);
+    }
+
+    if graph.weights.iter().all(|&weight| weight == 1) {
+        graph.weights.clear();
+    }
+    graph.rebalance_abscissas();
+    graph
+}
+
+/// Decompose the bottom layer of a graph, solve the components in parallel with a solver,
+/// and concatenate their solutions. Components with a single node are already solved.
+/// Returns the proof status of the whole solution, which is optimal if all the components are,
+/// and the statistics of each component from left to right, which are also sent to the observer.
+/// The events of each component are tagged with its index, e.g. `"component 3"`.
+pub fn solve_decomposed(
+    graph: &mut AbscissaGraph,
+    solver: &dyn Solver,
+    options: &SolveOptions,
+) -> (ProofStatus, Vec<ComponentStats>) {
+    let decomposition = Decomposition::new(graph);
+
+    let results: Vec<(Vec<usize>, ComponentStats)> = (0..decomposition.components().len())
+        .into_par_iter()
+        .map(|component| {
+            let component_options = SolveOptions {
+                free_layer: FreeLayer::Bottom,
+                decompose: false,
+                observer: component_observer(&options.observer, component),
+                precedence: options.precedence.as_ref().map(|precedence| {
+                    Arc::new(precedence.subset(&decomposition.components()[component]))
+                }),
+                ..options.clone()
+            };
+            let mut component_graph = decomposition.component_graph(graph, component);
+            let initial_crossings = count_crossings(&component_graph);
+
+            let start_time = Instant::now();
+            let solver_stats = if component_graph.bottom_nodes_abscissas.len() > 1 {
+                solve_in_place(&mut component_graph, solver, &component_options)
+            } else {
+                SolverStats {
+                    status: ProofStatus::Optimal,
+                    iterations: 0,
+                }
+            };
+            let elapsed_time = start_time.elapsed();
+
+            let stats = ComponentStats {
+                bottom_node_count: component_graph.bottom_nodes_abscissas.len(),
+                top_node_count: component_graph.top_nodes_abscissas.len(),
+                edge_count: component_graph.edges.len(),
+                initial_crossings,
+                final_crossings: count_crossings(&component_graph),
+                elapsed_time,
+                status: solver_stats.status,
+                iterations: solver_stats.iterations,
+            };
+            (component_graph.bottom_order(), stats)
+        })
+        .collect();
+
+    let (component_orders, stats): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+    graph.set_bottom_order(&decomposition.stitch(&component_orders));
+    for (component, component_stats) in stats.iter().enumerate() {
+        component_observer(&options.observer, component).notify(SolverEvent::ComponentSolved {
+            stats: *component_stats,
+        });
+    }
+
+    let status = if stats.iter().all(|s| s.status == ProofStatus::Optimal) {
+        ProofStatus::Optimal
+    } else {
+        ProofStatus::Feasible
+    };
+
+    (status, stats)
+}
+
+/// Observer of the events of a component, tagged with its index from left to right
+fn component_observer(observer: &EventObserver, component: usize) -> EventObserver {
+    observer.with_source(&format!("component {}", component))
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::algorithms::Algorithm;
+
+    #[test]
+    fn interval_blocks_split_non_overlapping_nodes() {
+        // Nodes 0 and 1 only share the top node 2, node 3 overlaps node 1, node 2 is isolated
+        let neighbors = vec![
+            vec![(0, 1), (2, 1)],
+            vec![(2, 1), (5, 1)],
+            vec![],
+            vec![(3, 1), (4, 1)],
+            vec![(5, 1), (6, 1)],
+        ];
+        assert_eq!(
+            interval_blocks(&neighbors),
+            vec![vec![0], vec![1, 3], vec![4]]
+        );
+    }
+
+    #[test]
+    fn decomposed_solve_adds_up_components() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/medium/58.gr").unwrap()).into();
+        let options = SolveOptions {
+            decompose: true,
+            ..Default::default()
+        };
+
+        let mut decomposed = graph.clone();
+        let (_, stats) = solve_decomposed(&mut decomposed, &Algorithm::IterBarycenter, &options);
+
+        let mut order = decomposed.bottom_order();
+        order.sort_unstable();
+        assert_eq!(
+            order,
+            (0..graph.bottom_nodes_abscissas.len()).collect::<Vec<_>>()
+        );
+
+        // There are no crossings between components
+        let total: u64 = stats.iter().map(|s| s.final_crossings).sum();
+        assert_eq!(count_crossings(&decomposed), total);
+        let initial_total: u64 = stats.iter().map(|s| s.initial_crossings).sum();
+        assert!(initial_total <= count_crossings(&graph));
+    }
+}
@@ -0,0 +1,210 @@
+//! Structured progress events of the solvers, sent to an observer instead of being printed.
+//!
+//! The command line prints them, a GUI can animate them, and tests can assert on them.
+
+use std::{
+    fmt::{Debug, Formatter},
+    sync::Arc,
+    time::Duration,
+};
+
+use crate::{
+    algorithms::{ProofStatus, Refinement},
+    decomposition::ComponentStats,
+};
+
+/// Progress event of an iterative solver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolverEvent {
+    /// An iteration starts (iterations are numbered from 1)
+    IterationStarted { iteration: u64 },
+    /// Crossing count after an iteration
+    Crossings { iteration: u64, crossings: u64 },
+    /// The iteration removed crossings
+    Improved { iteration: u64, crossings: u64 },
+    /// The last iteration added crossings and was reverted, back to `crossings`
+    Reverted { iteration: u64, crossings: u64 },
+    /// The solver stopped after `iterations` iterations, with `crossings` crossings
+    Finished { iterations: u64, crossings: u64 },
+    /// A member of a portfolio finished with `crossings` crossings, and its solution was kept or not
+    MemberFinished {
+        crossings: u64,
+        elapsed_time: Duration,
+        status: ProofStatus,
+        kept: bool,
+    },
+    /// A randomized solver draws its random numbers from `seed`, which reproduces its run
+    Seed { seed: u64 },
+    /// The free layer, or one of its independent blocks, has `nodes` nodes, more than the `max_nodes` supported by
+    /// the solver, which falls back to a heuristic
+    Fallback { nodes: usize, max_nodes: usize },
+    /// The free layer has `nodes` nodes, too many to precompute all the pairwise crossing counts:
+    /// they are computed on demand, see [`SparseCrossingMatrix`](crate::crossing_matrix::SparseCrossingMatrix)
+    SparseCrossingMatrix { nodes: usize },
+    /// The reduction merged `bottom_nodes` bottom nodes into `reduced_nodes` nodes, split into `blocks` blocks
+    Reduced {
+        bottom_nodes: usize,
+        reduced_nodes: usize,
+        blocks: usize,
+    },
+    /// A component of the decomposition was solved
+    ComponentSolved { stats: ComponentStats },
+    /// The refinement removed `removed` crossings after the solver
+    Refined {
+        refinement: Refinement,
+        removed: u64,
+    },
+}
+
+/// Receiver of solver events. `source` names the solver that sent the event when several solvers
+/// share the same observer, e.g. `"iterated_median"` for a member of a portfolio (see [`EventObserver::with_source`])
+pub trait Observer: Send + Sync {
+    fn notify(&self, source: Option<&str>, event: &SolverEvent);
+}
+
+impl<F> Observer for F
+where
+    F: Fn(Option<&str>, &SolverEvent) + Send + Sync,
+{
+    fn notify(&self, source: Option<&str>, event: &SolverEvent) {
+        self(source, event)
+    }
+}
+
+/// Shared observer of the solver events. Events are ignored when there is no observer (by default)
+#[derive(Clone, Default)]
+pub struct EventObserver {
+    observer: Option<Arc<dyn Observer>>,
+    source: Option<Arc<str>>,
+}
+
+impl EventObserver {
+    /// Send the events to an observer, for instance a closure
+    pub fn new(observer: impl Observer + 'static) -> Self {
+        EventObserver {
+            observer: Some(Arc::new(observer)),
+            source: None,
+        }
+    }
+
+    /// Send the events to the same observer, tagged with the name of the solver that sends them.
+    /// Nested sources are joined with a `/`, e.g. `"portfolio/iterated_median"`
+    pub fn with_source(&self, source: &str) -> Self {
+        EventObserver {
+            observer: self.observer.clone(),
+            source: Some(match &self.source {
+                Some(parent) => format!("{}/{}", parent, source).into(),
+                None => source.into(),
+            }),
+        }
+    }
+
+    /// Send an event to the observer, if any
+    pub fn notify(&self, event: SolverEvent) {
+        if let Some(observer) = &self.observer {
+            observer.notify(self.source.as_deref(), &event);
+        }
+    }
+}
+
+impl Debug for EventObserver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EventObserver")
+            .field("observer", &self.observer.is_some())
+            .field("source", &self.source)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::{
+        algorithms::{solve_in_place, Algorithm, SolveOptions},
+        crossings::count_crossings,
+        graphs::abscissa_graph::AbscissaGraph,
+    };
+
+    #[test]
+    fn iterated_heuristics_report_their_progress() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/medium/58.gr").unwrap()).into();
+
+        for algorithm in [Algorithm::IterMedian, Algorithm::IterBarycenter] {
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let recorder = Arc::clone(&events);
+            let options = SolveOptions {
+                observer: EventObserver::new(move |_: Option<&str>, event: &SolverEvent| {
+                    recorder.lock().unwrap().push(*event)
+                }),
+                ..Default::default()
+            };
+
+            let mut solution = graph.clone();
+            let stats = solve_in_place(&mut solution, &algorithm, &options);
+            let events = events.lock().unwrap();
+
+            assert_eq!(events[0], SolverEvent::IterationStarted { iteration: 1 });
+            assert_eq!(
+                *events.last().unwrap(),
+                SolverEvent::Finished {
+                    iterations: stats.iterations,
+                    crossings: count_crossings(&solution),
+                }
+            );
+
+            let crossings: Vec<u64> = events
+                .iter()
+                .filter_map(|event| match event {
+                    SolverEvent::Crossings { crossings, .. } => Some(*crossings),
+                    _ => None,
+                })
+                .collect();
+            assert_eq!(crossings.len() as u64, stats.iterations, "{}", algorithm);
+
+            // Every iteration but the last one improved the crossing count
+            let improvements = events
+                .iter()
+                .filter(|event| matches!(event, SolverEvent::Improved { .. }))
+                .count();
+            assert!(improvements + 1 >= crossings.len(), "{}", algorithm);
+        }
+    }
+
+    #[test]
+    fn diagnostics_are_reported_to_the_observer() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/tiny/website_20.gr").unwrap()).into();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Arc::clone(&events);
+        let options = SolveOptions {
+            reduce: true,
+            refinement: Some(Refinement::AdjacentExchange),
+            observer: EventObserver::new(move |_: Option<&str>, event: &SolverEvent| {
+                recorder.lock().unwrap().push(*event)
+            }),
+            ..Default::default()
+        };
+
+        let mut solution = graph.clone();
+        solve_in_place(&mut solution, &Algorithm::Sifting, &options);
+        let events = events.lock().unwrap();
+
+        assert!(events.iter().any(|event| matches!(
+            event,
+            SolverEvent::Reduced {
+                bottom_nodes: 10,
+                ..
+            }
+        )));
+        assert!(matches!(
+            events.last(),
+            Some(SolverEvent::Refined {
+                refinement: Refinement::AdjacentExchange,
+                ..
+            })
+        ));
+    }
+}
@@ -1,9 +1,13 @@
 use ocm_parser::{
     bipartite_graph::BipartiteGraph,
-    graph_base::{Edge, OrderedGraph},
+    graph_base::{Edge, OrderedGraph, WeightedEdge},
 };
 
-use crate::algo_utils::sorted_index_array;
+use crate::algo_utils::{rank_index_array, sorted_index_array};
+
+/// Top neighbor of a bottom node, as `(rank, weight)`: the rank of the top node in the top layer order,
+/// and the total weight of the edges between both nodes
+pub type WeightedRank = (u64, u64);
 
 /// Abscissa-based graph data structure, where each node has an abscissa attributed at construction.
 /// By convention, we space all vertices evenly among the top and bottom, such that the extremal vertices
@@ -17,6 +21,8 @@ pub struct AbscissaGraph {
 
     /// Edges between the top and bottom nodes
     pub edges: Vec<Edge>,
+    /// Weight of each edge, in the same order as the edges. Empty if all edges have a weight of 1
+    pub weights: Vec<u64>,
 }
 
 impl AbscissaGraph {
@@ -25,6 +31,15 @@ impl AbscissaGraph {
         Self::default()
     }
 
+    /// Weight of the edge at the given index
+    pub fn weight(&self, index: usize) -> u64 {
+        if self.weights.is_empty() {
+            1
+        } else {
+            self.weights[index]
+        }
+    }
+
     /// Rebalance and symmetrize the graph abscissas for clean display.
     /// Call this method after updating vertex abscissas using a barycentric or median heuristic method.
     pub fn rebalance_abscissas(&mut self) {
@@ -56,6 +71,58 @@ impl AbscissaGraph {
             self.bottom_nodes_abscissas[old_index] = index as f64 * scale + bottom_offset;
         }
     }
+
+    /// For each bottom node, the ranks of its top neighbors in increasing order, with the total weight
+    /// of the edges to each of them (parallel edges are merged, saturating at `u64::MAX`), see [`WeightedRank`].
+    pub fn bottom_neighbors(&self) -> Vec<Vec<WeightedRank>> {
+        let top_ranks = rank_index_array(&self.top_nodes_abscissas);
+
+        let mut neighbors: Vec<Vec<WeightedRank>> = vec![vec![]; self.bottom_nodes_abscissas.len()];
+        for (index, (top_index, bottom_index)) in self.edges.iter().enumerate() {
+            let rank = top_ranks[*top_index as usize] as u64;
+            neighbors[*bottom_index as usize].push((rank, self.weight(index)));
+        }
+        for node_neighbors in &mut neighbors {
+            node_neighbors.sort_unstable();
+            node_neighbors.dedup_by(|(rank, weight), (previous_rank, previous_weight)| {
+                let parallel = rank == previous_rank;
+                if parallel {
+                    *previous_weight = previous_weight.saturating_add(*weight);
+                }
+                parallel
+            });
+        }
+
+        neighbors
+    }
+
+    /// Swap the top and bottom layers, so that top layer algorithms can reuse the bottom layer ones
+    pub fn transposed(&self) -> Self {
+        AbscissaGraph {
+            top_nodes_abscissas: self.bottom_nodes_abscissas.clone(),
+            bottom_nodes_abscissas: self.top_nodes_abscissas.clone(),
+            edges: self
+                .edges
+                .iter()
+                .map(|(top_index, bottom_index)| (*bottom_index, *top_index))
+                .collect(),
+            weights: self.weights.clone(),
+        }
+    }
+
+    /// Bottom node indices, from left to right
+    pub fn bottom_order(&self) -> Vec<usize> {
+        sorted_index_array(&self.bottom_nodes_abscissas)
+    }
+
+    /// Place the bottom nodes in the given left to right order, then rebalance the abscissas.
+    /// `order` must be a permutation of the bottom node indices.
+    pub fn set_bottom_order(&mut self, order: &[usize]) {
+        for (position, &index) in order.iter().enumerate() {
+            self.bottom_nodes_abscissas[index] = position as f64;
+        }
+        self.rebalance_abscissas();
+    }
 }
 
 /// Implement the conversion from a bipartite graph reference (does not consume the original one)
@@ -70,6 +137,7 @@ impl From<&BipartiteGraph> for AbscissaGraph {
             .iter()
             .map(|(top_index, bottom_index)| (top_index - 1, bottom_index - 1 - top_count))
             .collect();
+        graph.weights = origin.weights.clone();
 
         // Compute the absissas
         let max_row_node_count = origin.bottom_node_count.max(origin.top_node_count);
@@ -101,9 +169,9 @@ impl From<&AbscissaGraph> for BipartiteGraph {
         let mut graph = BipartiteGraph::new();
 
         // The BipartiteGraph lists its node indices starting from 1, left to right.
-        // The AbscissaGraph node abscissas are not in order, we need to compute their sorted indices and update the edges
-        let top_indices = sorted_index_array(&origin.top_nodes_abscissas);
-        let bottom_indices = sorted_index_array(&origin.bottom_nodes_abscissas);
+        // The AbscissaGraph node abscissas are not in order, we need to compute their ranks and update the edges
+        let top_indices = rank_index_array(&origin.top_nodes_abscissas);
+        let bottom_indices = rank_index_array(&origin.bottom_nodes_abscissas);
 
         // Clone the edges and reset their indices back to the BipartiteGraph format
         // (top ones start at 1, bottom ones start at top_count + 1)
@@ -118,6 +186,7 @@ impl From<&AbscissaGraph> for BipartiteGraph {
                 )
             })
             .collect();
+        graph.weights = origin.weights.clone();
 
         // Fill the nodes. Keep in mind that the edges assume that the indices start from 1.
         graph.top_node_count = origin.top_nodes_abscissas.len() as u64;
@@ -130,9 +199,9 @@ impl From<&AbscissaGraph> for BipartiteGraph {
 /// Implement the OrderedGraph trait for the AbscissaGraph
 impl OrderedGraph for AbscissaGraph {
     fn get_ordered_edges(&self) -> Vec<Edge> {
-        // The AbscissaGraph node abscissas are not in order, we need to compute their sorted indices and update the edges
-        let top_indices = sorted_index_array(&self.top_nodes_abscissas);
-        let bottom_indices = sorted_index_array(&self.bottom_nodes_abscissas);
+        // The AbscissaGraph node abscissas are not in order, we need to compute their ranks and update the edges
+        let top_indices = rank_index_array(&self.top_nodes_abscissas);
+        let bottom_indices = rank_index_array(&self.bottom_nodes_abscissas);
 
         self.edges
             .iter()
@@ -144,4 +213,47 @@ impl OrderedGraph for AbscissaGraph {
             })
             .collect()
     }
+
+    fn get_ordered_weighted_edges(&self) -> Vec<WeightedEdge> {
+        self.get_ordered_edges()
+            .into_iter()
+            .enumerate()
+            .map(|(index, (top, bottom))| (top, bottom, self.weight(index)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion_relabels_nodes_by_rank() {
+        // Bottom node 0 is moved to the right of bottom nodes 1 and 2
+        let graph = AbscissaGraph {
+            top_nodes_abscissas: vec![0.0, 1.0, 2.0],
+            bottom_nodes_abscissas: vec![2.5, 0.5, 1.5],
+            edges: vec![(0, 0), (1, 1), (2, 2)],
+            ..Default::default()
+        };
+
+        let bipartite: BipartiteGraph = (&graph).into();
+        assert_eq!(bipartite.edges, vec![(1, 6), (2, 4), (3, 5)]);
+        assert_eq!(graph.get_ordered_edges(), vec![(0, 2), (1, 0), (2, 1)]);
+    }
+
+    #[test]
+    fn bottom_order_round_trip() {
+        let mut graph: AbscissaGraph = (&BipartiteGraph {
+            top_node_count: 2,
+            bottom_node_count: 3,
+            edges: vec![(1, 3), (2, 4), (2, 5)],
+            ..Default::default()
+        })
+            .into();
+        assert_eq!(graph.bottom_order(), vec![0, 1, 2]);
+
+        graph.set_bottom_order(&[2, 0, 1]);
+        assert_eq!(graph.bottom_order(), vec![2, 0, 1]);
+    }
 }
@@ -1,7 +1,16 @@
 pub mod algo_utils;
 pub mod algorithms;
+pub mod cancellation;
+pub mod crossing_matrix;
 pub mod crossings;
+pub mod decomposition;
+pub mod events;
 pub mod graphs;
+pub mod lower_bound;
+pub mod penalty_graph;
+pub mod precedence;
+pub mod reduction;
+pub mod solver;
 
 pub fn add(left: usize, right: usize) -> usize {
     left + right
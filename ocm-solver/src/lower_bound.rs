@@ -0,0 +1,197 @@
+//! Lower bounds on the number of crossings of the one-sided problem, to measure how far a solution
+//! can be from the optimum.
+//!
+//! 1. Whatever the order, the bottom nodes `u` and `v` cross at least `min(c(u, v), c(v, u))` times.
+//!
+//! 2. If the cheapest orders of three nodes form a cycle (`u` before `v`, `v` before `w` and `w` before `u`),
+//!    one of the three pairs has to be placed in its most expensive order. The bound of the triangles that
+//!    do not share a pair can be added to the pairwise bound.
+//!
+//! 3. Nodes of different interval blocks (see [`interval_blocks`]) never cross when placed in their cheapest order,
+//!    so each block is bounded separately, and small blocks are solved exactly.
+
+use crate::{
+    algorithms::{exact_dp::exact_dp, FreeLayer},
+    crossing_matrix::{
+        CrossingMatrix, DenseCrossingMatrix, SparseCrossingMatrix, DENSE_MATRIX_MAX_SIZE,
+    },
+    decomposition::interval_blocks,
+    graphs::abscissa_graph::{AbscissaGraph, WeightedRank},
+};
+
+/// Largest block size solved exactly by dynamic programming
+const EXACT_BLOCK_MAX_NODES: usize = 12;
+/// Largest block size for which the triangle bound is computed (`O(n^3)`)
+const TRIANGLE_BLOCK_MAX_NODES: usize = 256;
+
+/// Lower bound on the number of crossings of any order of the free layer of a graph.
+/// There is no cheap bound when both layers are free, so it is 0 in that case.
+pub fn lower_bound(graph: &AbscissaGraph, free_layer: FreeLayer) -> u64 {
+    match free_layer {
+        FreeLayer::Bottom => bottom_lower_bound(&graph.bottom_neighbors()),
+        FreeLayer::Top => bottom_lower_bound(&graph.transposed().bottom_neighbors()),
+        FreeLayer::Both => 0,
+    }
+}
+
+/// Lower bound on the number of crossings of any bottom order, from the weighted top neighbor ranks
+/// of each bottom node.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(n^3)` per block of `n` nodes up to 256 nodes, `O(n^2 * d)` up to 2048 nodes (where `d` is the
+///   average degree), and `O(P * d)` for larger blocks, where `P` is the number of overlapping pairs
+/// * Space: `O(n^2)` for the largest block of at most 2048 nodes
+pub fn bottom_lower_bound(neighbors: &[Vec<WeightedRank>]) -> u64 {
+    interval_blocks(neighbors)
+        .iter()
+        .map(|block| {
+            let block_neighbors: Vec<Vec<WeightedRank>> =
+                block.iter().map(|&u| neighbors[u].clone()).collect();
+
+            if let Some((_, crossings)) = exact_dp(&block_neighbors, EXACT_BLOCK_MAX_NODES) {
+                crossings
+            } else if block.len() <= DENSE_MATRIX_MAX_SIZE {
+                let matrix = DenseCrossingMatrix::from_neighbors(&block_neighbors);
+                let triangles = if block.len() <= TRIANGLE_BLOCK_MAX_NODES {
+                    triangle_bound(&matrix)
+                } else {
+                    0
+                };
+                pairwise_bound(&matrix) + triangles
+            } else {
+                let matrix = SparseCrossingMatrix::from_neighbors(block_neighbors);
+                matrix
+                    .overlapping_pairs()
+                    .into_iter()
+                    .map(|(u, v)| matrix.crossings(u, v).min(matrix.crossings(v, u)))
+                    .sum()
+            }
+        })
+        .sum()
+}
+
+/// Sum of `min(c(u, v), c(v, u))` over all pairs of nodes
+pub fn pairwise_bound(matrix: &impl CrossingMatrix) -> u64 {
+    let n = matrix.size();
+    (0..n)
+        .flat_map(|u| (u + 1..n).map(move |v| (u, v)))
+        .map(|(u, v)| matrix.crossings(u, v).min(matrix.crossings(v, u)))
+        .sum()
+}
+
+/// Extra crossings forced by cycles of three nodes in the cheapest pair orders, on top of [`pairwise_bound`].
+///
+/// Algorithm
+/// ---------
+/// Greedily pick the cycles whose pairs are not used by a previous cycle.
+/// Each one adds the smallest cost of reversing one of its pairs, `|c(u, v) - c(v, u)|`.
+///
+/// Complexity
+/// ----------
+/// * Time: `O(n^3)`
+/// * Space: `O(n^2)`
+pub fn triangle_bound(matrix: &impl CrossingMatrix) -> u64 {
+    let n = matrix.size();
+    // Signed cost of placing u before v rather than v before u
+    let delta =
+        |u: usize, v: usize| matrix.crossings(u, v) as i128 - matrix.crossings(v, u) as i128;
+    let mut used = vec![false; n * n];
+    let mut bound = 0_u64;
+
+    for u in 0..n {
+        for v in u + 1..n {
+            let uv = delta(u, v);
+            if uv == 0 || used[u * n + v] {
+                continue;
+            }
+            for w in v + 1..n {
+                if used[u * n + v] {
+                    break;
+                }
+                let (vw, uw) = (delta(v, w), delta(u, w));
+                // Cycle u -> v -> w -> u when u < v, v < w and w < u are cheaper, or the opposite cycle
+                let cycle = (uv < 0 && vw < 0 && uw > 0) || (uv > 0 && vw > 0 && uw < 0);
+                if !cycle || used[u * n + w] || used[v * n + w] {
+                    continue;
+                }
+                // The differences of two crossing counts fit in a u64
+                let cost = uv
+                    .unsigned_abs()
+                    .min(vw.unsigned_abs())
+                    .min(uw.unsigned_abs());
+                bound = bound.saturating_add(cost as u64);
+                used[u * n + v] = true;
+                used[u * n + w] = true;
+                used[v * n + w] = true;
+            }
+        }
+    }
+
+    bound
+}
+
+/// Relative gap between a number of crossings and a lower bound, in `[0, 1]` (0 when the solution is proven optimal)
+pub fn relative_gap(crossings: u64, lower_bound: u64) -> f64 {
+    if crossings == 0 {
+        0.0
+    } else {
+        crossings.saturating_sub(lower_bound) as f64 / crossings as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::{
+        algorithms::{solve_in_place, Algorithm, SolveOptions},
+        crossings::count_crossings,
+    };
+
+    #[test]
+    fn lower_bound_is_below_the_optimum() {
+        for file in [
+            "website_20",
+            "complete_4_5",
+            "cycle_8_sorted",
+            "plane_5_6",
+            "grid_9_shuffled",
+        ] {
+            let graph: AbscissaGraph =
+                (&parse_file(&format!("../datasets/tiny/{}.gr", file)).unwrap()).into();
+            let neighbors = graph.bottom_neighbors();
+            let (_, optimum) = exact_dp(&neighbors, 20).unwrap();
+
+            let matrix = DenseCrossingMatrix::from_neighbors(&neighbors);
+            assert!(
+                pairwise_bound(&matrix) + triangle_bound(&matrix) <= optimum,
+                "{}",
+                file
+            );
+            assert!(
+                lower_bound(&graph, FreeLayer::Bottom) <= optimum,
+                "{}",
+                file
+            );
+        }
+    }
+
+    #[test]
+    fn lower_bound_is_below_heuristic_solutions() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/medium/58.gr").unwrap()).into();
+        let bound = lower_bound(&graph, FreeLayer::Bottom);
+        let matrix = DenseCrossingMatrix::new(&graph);
+        assert!(bound >= pairwise_bound(&matrix));
+
+        let mut solution = graph.clone();
+        solve_in_place(&mut solution, &Algorithm::Sifting, &SolveOptions::default());
+        let crossings = count_crossings(&solution);
+        assert!(bound <= crossings);
+
+        let gap = relative_gap(crossings, bound);
+        assert!((0.0..=1.0).contains(&gap));
+        assert_eq!(relative_gap(0, 0), 0.0);
+    }
+}
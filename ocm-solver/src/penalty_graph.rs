@@ -0,0 +1,124 @@
+//! Penalty digraph of the bottom nodes, which reformulates the one-sided problem as a weighted feedback arc set problem.
+//!
+//! For each pair of bottom nodes with `c(u, v) < c(v, u)`, the arc `u -> v` has the weight `c(v, u) - c(u, v)`:
+//! it is the penalty paid when `v` is placed to the left of `u`. The crossing count of a bottom order is the sum of
+//! `min(c(u, v), c(v, u))` over all pairs, plus the weight of the arcs going backwards in the order.
+//! Minimizing the crossings is therefore equivalent to finding a minimum weight set of arcs whose removal makes
+//! the graph acyclic, and ordering the nodes topologically.
+
+use ocm_parser::bipartite_graph::BipartiteGraph;
+
+use crate::{
+    crossing_matrix::{crossing_matrix, CrossingMatrix},
+    graphs::abscissa_graph::AbscissaGraph,
+};
+
+/// Weighted arc towards a node
+pub type WeightedArc = (usize, u64);
+
+/// Penalty digraph of the bottom nodes, with both the outgoing and incoming arcs of each node
+#[derive(Debug, Clone, Default)]
+pub struct PenaltyGraph {
+    successors: Vec<Vec<WeightedArc>>,
+    predecessors: Vec<Vec<WeightedArc>>,
+    base_crossings: u64,
+}
+
+impl PenaltyGraph {
+    /// Build the penalty graph of the bottom nodes of a graph
+    pub fn new(graph: &AbscissaGraph) -> Self {
+        Self::from_matrix(&*crossing_matrix(graph))
+    }
+
+    /// Build the penalty graph from a crossing matrix.
+    ///
+    /// Complexity
+    /// ----------
+    /// * Time: `O(V^2)` crossing matrix queries
+    /// * Space: `O(V + A)` where `A` is the number of arcs
+    pub fn from_matrix<M: CrossingMatrix + ?Sized>(matrix: &M) -> Self {
+        let size = matrix.size();
+        let mut successors = vec![Vec::new(); size];
+        let mut predecessors = vec![Vec::new(); size];
+        let mut base_crossings = 0;
+
+        for u in 0..size {
+            for v in u + 1..size {
+                let (uv, vu) = (matrix.crossings(u, v), matrix.crossings(v, u));
+                base_crossings += uv.min(vu);
+                let (from, to) = if uv < vu { (u, v) } else { (v, u) };
+                if uv != vu {
+                    successors[from].push((to, uv.abs_diff(vu)));
+                    predecessors[to].push((from, uv.abs_diff(vu)));
+                }
+            }
+        }
+
+        PenaltyGraph {
+            successors,
+            predecessors,
+            base_crossings,
+        }
+    }
+
+    /// Number of nodes
+    pub fn size(&self) -> usize {
+        self.successors.len()
+    }
+
+    /// Arcs leaving a node: the nodes that it should precede, with the penalty of placing them before it
+    pub fn successors(&self, u: usize) -> &[WeightedArc] {
+        &self.successors[u]
+    }
+
+    /// Arcs entering a node: the nodes that should precede it, with the penalty of placing them after it
+    pub fn predecessors(&self, u: usize) -> &[WeightedArc] {
+        &self.predecessors[u]
+    }
+
+    /// Crossings of a bottom order in which every pair is in its cheapest order, i.e. without any backward arc
+    pub fn base_crossings(&self) -> u64 {
+        self.base_crossings
+    }
+
+    /// Total weight of the arcs going backwards in a bottom order (from right to left)
+    pub fn backward_weight(&self, order: &[usize]) -> u64 {
+        let mut position = vec![0; self.size()];
+        order
+            .iter()
+            .enumerate()
+            .for_each(|(index, &u)| position[u] = index);
+
+        (0..self.size())
+            .flat_map(|u| {
+                self.successors[u]
+                    .iter()
+                    .map(move |&(v, weight)| (u, v, weight))
+            })
+            .filter(|&(u, v, _)| position[v] < position[u])
+            .map(|(_, _, weight)| weight)
+            .sum()
+    }
+
+    /// Number of crossings of a bottom order
+    pub fn crossings(&self, order: &[usize]) -> u64 {
+        self.base_crossings + self.backward_weight(order)
+    }
+}
+
+impl From<&BipartiteGraph> for PenaltyGraph {
+    fn from(graph: &BipartiteGraph) -> Self {
+        Self::new(&graph.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crossings::tests::assert_counts_match;
+
+    #[test]
+    fn penalty_graph_counts_crossings() {
+        assert_counts_match(|graph, order| PenaltyGraph::new(graph).crossings(order));
+    }
+}
@@ -0,0 +1,225 @@
+//! Precedence constraints between the bottom nodes whose relative order is forced (the bottom layer is the free one).
+//!
+//! If `c(u, v) = 0 < c(v, u)`, `u` comes before `v` in every optimal order: when `v` is before `u`, either moving `u`
+//! right before `v` or moving `v` right after `u` removes crossings. `c(u, v) = 0` when all the neighbor ranks of `u`
+//! are lower than or equal to the ones of `v`, so the forced pairs form an interval order:
+//! `u` is forced before `v` if `last(u) <= first(v)` and `first(u) < last(v)`, where `first` and `last` are the lowest
+//! and highest neighbor ranks of a node. The second condition only excludes the pairs of nodes with a single neighbor
+//! of the same rank, which never cross.
+//!
+//! The constraints are stored as two keys per node, so that a pair is checked in constant time.
+//! The solvers receive them through [`SolveOptions::precedence`](crate::algorithms::SolveOptions::precedence).
+
+use std::{cmp::Reverse, collections::BinaryHeap, ops::RangeInclusive};
+
+use crate::graphs::abscissa_graph::{AbscissaGraph, WeightedRank};
+
+/// Forced relative orders of the bottom nodes of a graph
+#[derive(Debug, Clone, Default)]
+pub struct Precedence {
+    /// `2 * first + 1`, minus 1 for the nodes with a single neighbor rank (0 for isolated nodes)
+    start: Vec<u64>,
+    /// `2 * last`, plus 1 for the nodes with a single neighbor rank (`u64::MAX` for isolated nodes).
+    /// `u` is forced before `v` if and only if `end[u] <= start[v]`
+    end: Vec<u64>,
+}
+
+impl Precedence {
+    /// Compute the forced pairs of the bottom nodes of a graph
+    pub fn new(graph: &AbscissaGraph) -> Self {
+        Self::from_neighbors(&graph.bottom_neighbors())
+    }
+
+    /// Compute the forced pairs from the weighted top neighbor ranks of each bottom node, sorted by rank
+    pub fn from_neighbors(neighbors: &[Vec<WeightedRank>]) -> Self {
+        let (start, end) = neighbors
+            .iter()
+            .map(|neighbors| match (neighbors.first(), neighbors.last()) {
+                (Some(&(first, _)), Some(&(last, _))) => {
+                    let single = (first == last) as u64;
+                    (2 * first + 1 - single, 2 * last + single)
+                }
+                _ => (0, u64::MAX), // Isolated nodes never cross
+            })
+            .unzip();
+
+        Precedence { start, end }
+    }
+
+    /// Number of bottom nodes
+    pub fn size(&self) -> usize {
+        self.start.len()
+    }
+
+    /// Constraints between a subset of the nodes, whose indices become their positions in `nodes`
+    pub fn subset(&self, nodes: &[usize]) -> Self {
+        Precedence {
+            start: nodes.iter().map(|&u| self.start[u]).collect(),
+            end: nodes.iter().map(|&u| self.end[u]).collect(),
+        }
+    }
+
+    /// Whether `u` must be placed before `v`, i.e. `c(u, v) = 0 < c(v, u)`
+    pub fn is_forced(&self, u: usize, v: usize) -> bool {
+        self.end[u] <= self.start[v]
+    }
+
+    /// Whether no node of an order is placed after a node that is forced after it
+    ///
+    /// Complexity
+    /// ----------
+    /// * Time: `O(V)`
+    /// * Space: `O(V)`
+    pub fn respects(&self, order: &[usize]) -> bool {
+        // Smallest end key among the following nodes
+        let mut min_end = u64::MAX;
+        for &v in order.iter().rev() {
+            if min_end <= self.start[v] {
+                return false;
+            }
+            min_end = min_end.min(self.end[v]);
+        }
+        true
+    }
+
+    /// Closest order to `order` that respects the constraints: the nodes are placed one by one,
+    /// each time the first node of `order` whose forced predecessors are all placed.
+    /// Orders that already respect the constraints are unchanged. `order` must be a permutation of the nodes.
+    ///
+    /// Algorithm
+    /// ---------
+    /// A node `v` can be placed when all the nodes left have an end key greater than its start key.
+    /// The nodes are released in the order of their start keys as the smallest end key left increases,
+    /// into a min-heap of their positions in `order`.
+    ///
+    /// Complexity
+    /// ----------
+    /// * Time: `O(V * log(V))`
+    /// * Space: `O(V)`
+    pub fn linear_extension(&self, order: &[usize]) -> Vec<usize> {
+        let mut priority = vec![0; self.size()];
+        for (position, &u) in order.iter().enumerate() {
+            priority[u] = position;
+        }
+        let mut by_start = order.to_vec();
+        by_start.sort_unstable_by_key(|&u| self.start[u]);
+        let mut by_end = order.to_vec();
+        by_end.sort_unstable_by_key(|&u| self.end[u]);
+
+        let mut placed = vec![false; self.size()];
+        let mut available = BinaryHeap::new();
+        let (mut next_start, mut next_end) = (0, 0);
+        let mut extension = Vec::with_capacity(order.len());
+        while extension.len() < order.len() {
+            // Smallest end key left. A node is never forced before itself, so the first one can be released
+            while placed[by_end[next_end]] {
+                next_end += 1;
+            }
+            let min_end = self.end[by_end[next_end]];
+            while next_start < by_start.len() && self.start[by_start[next_start]] < min_end {
+                let u = by_start[next_start];
+                available.push(Reverse((priority[u], u)));
+                next_start += 1;
+            }
+
+            let Reverse((_, u)) = available.pop().unwrap();
+            placed[u] = true;
+            extension.push(u);
+        }
+
+        extension
+    }
+
+    /// Positions where `v` can be inserted into the order of the other nodes, from after its last forced predecessor
+    /// to before its first forced successor. `v` itself is skipped if `order` contains it.
+    /// The range is empty if `order` does not respect the constraints.
+    ///
+    /// Complexity
+    /// ----------
+    /// * Time: `O(V)`
+    /// * Space: `O(1)`
+    pub fn insertion_window(&self, order: &[usize], v: usize) -> RangeInclusive<usize> {
+        let mut lowest = 0;
+        let mut highest = None;
+        let others = order.iter().filter(|&&u| u != v);
+        for (position, &u) in others.enumerate() {
+            if self.is_forced(u, v) {
+                lowest = position + 1;
+            }
+            if highest.is_none() && self.is_forced(v, u) {
+                highest = Some(position);
+            }
+        }
+
+        let other_count = order.len() - order.contains(&v) as usize;
+        lowest..=highest.unwrap_or(other_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::crossing_matrix::{CrossingMatrix, DenseCrossingMatrix};
+
+    #[test]
+    fn forced_pairs_match_the_crossing_matrix() {
+        for filename in [
+            "../datasets/tiny/cycle_8_shuffled.gr",
+            "../datasets/tiny/website_20.gr",
+            "../datasets/medium/58.gr",
+        ] {
+            let graph: AbscissaGraph = (&parse_file(filename).unwrap()).into();
+            let matrix = DenseCrossingMatrix::new(&graph);
+            let precedence = Precedence::new(&graph);
+
+            for u in 0..matrix.size() {
+                for v in 0..matrix.size() {
+                    let forced =
+                        u != v && matrix.crossings(u, v) == 0 && matrix.crossings(v, u) > 0;
+                    assert_eq!(
+                        precedence.is_forced(u, v),
+                        forced,
+                        "{}: {} {}",
+                        filename,
+                        u,
+                        v
+                    );
+                }
+            }
+
+            // The linear extension of any order respects the constraints, and keeps the orders that already do
+            let mut order: Vec<usize> = (0..matrix.size()).rev().collect();
+            order.rotate_left(matrix.size() / 3);
+            let extension = precedence.linear_extension(&order);
+            assert!(precedence.respects(&extension), "{}", filename);
+            assert_eq!(precedence.linear_extension(&extension), extension);
+
+            let mut sorted = extension.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..matrix.size()).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn insertion_window_is_between_forced_nodes() {
+        // 0 is forced before 2, and 2 before 3. Node 1 overlaps all of them
+        let neighbors = vec![
+            vec![(1, 1)],
+            vec![(0, 1), (6, 1)],
+            vec![(2, 1), (3, 1)],
+            vec![(4, 1)],
+        ];
+        let precedence = Precedence::from_neighbors(&neighbors);
+        assert!(precedence.is_forced(0, 2) && precedence.is_forced(2, 3));
+        assert!(!precedence.is_forced(0, 1) && !precedence.is_forced(1, 3));
+
+        assert_eq!(precedence.insertion_window(&[0, 1, 3], 2), 1..=2);
+        assert_eq!(precedence.insertion_window(&[1, 0, 2, 3], 2), 2..=2);
+        assert_eq!(precedence.insertion_window(&[0, 2, 3], 1), 0..=3);
+        assert!(precedence.respects(&[1, 0, 2, 3]));
+        assert!(!precedence.respects(&[2, 0, 1, 3]));
+        assert_eq!(precedence.linear_extension(&[3, 2, 1, 0]), vec![1, 0, 2, 3]);
+    }
+}
@@ -0,0 +1,275 @@
+//! Reversible reductions of the one-sided problem, applied before solving (the bottom layer is the free one).
+//!
+//! 1. Isolated bottom nodes never cross anything: they are removed, and placed at the end of the order.
+//! 2. Twin bottom nodes, with the same neighbors, can be placed next to each other in an optimal order:
+//!    they are merged into one node whose edge weights are multiplied by the number of twins.
+//! 3. The nodes are split into independent blocks, whose neighborhood intervals do not overlap:
+//!    all the nodes of a block are placed before the nodes of the following blocks without any crossing
+//!    (see [`crate::decomposition`]).
+//! 4. If `c(u, v) = 0 < c(v, u)`, `u` comes before `v` in every optimal order: these forced pairs are fixed before
+//!    solving. Each block starts from an order respecting them, and its solver receives them as precedence constraints
+//!    (see [`crate::precedence`]), so the block solutions are lifted back without any change.
+
+use ahash::AHashMap;
+
+use crate::{
+    algorithms::ProofStatus,
+    decomposition::{bottom_subgraph, interval_blocks},
+    events::{EventObserver, SolverEvent},
+    graphs::abscissa_graph::{AbscissaGraph, WeightedRank},
+    precedence::Precedence,
+};
+
+/// Reduced instance, and the information needed to lift its solution back to the original bottom nodes
+#[derive(Debug, Clone)]
+pub struct Reduction {
+    /// Original bottom nodes merged in each reduced node, in index order
+    classes: Vec<Vec<usize>>,
+    /// Weighted top neighbor ranks of each reduced node, multiplied by the number of merged nodes
+    neighbors: Vec<Vec<WeightedRank>>,
+    /// Reduced nodes of each block, blocks from left to right
+    blocks: Vec<Vec<usize>>,
+    /// Isolated original bottom nodes
+    isolated: Vec<usize>,
+}
+
+impl Reduction {
+    /// Reduce the bottom layer of a graph
+    ///
+    /// Complexity
+    /// ----------
+    /// * Time: `O(V * log(V) + E * log(E))`
+    /// * Space: `O(V + E)`
+    pub fn new(graph: &AbscissaGraph) -> Self {
+        let bottom_neighbors = graph.bottom_neighbors();
+
+        // Isolated nodes, and twin classes of the other nodes
+        let mut isolated = Vec::new();
+        let mut classes: Vec<Vec<usize>> = Vec::new();
+        let mut class_index: AHashMap<&[WeightedRank], usize> = AHashMap::new();
+        for (u, neighbors) in bottom_neighbors.iter().enumerate() {
+            if neighbors.is_empty() {
+                isolated.push(u);
+                continue;
+            }
+            let class = *class_index.entry(neighbors).or_insert_with(|| {
+                classes.push(vec![]);
+                classes.len() - 1
+            });
+            classes[class].push(u);
+        }
+
+        let neighbors: Vec<Vec<WeightedRank>> = classes
+            .iter()
+            .map(|class| {
+                bottom_neighbors[class[0]]
+                    .iter()
+                    .map(|&(rank, weight)| (rank, weight.saturating_mul(class.len() as u64)))
+                    .collect()
+            })
+            .collect();
+
+        // Independent blocks of the reduced nodes
+        let blocks = interval_blocks(&neighbors);
+
+        Reduction {
+            classes,
+            neighbors,
+            blocks,
+            isolated,
+        }
+    }
+
+    /// Number of reduced nodes
+    pub fn node_count(&self) -> usize {
+        self.classes.len()
+    }
+
+    /// Independent blocks of reduced nodes, from left to right
+    pub fn blocks(&self) -> &[Vec<usize>] {
+        &self.blocks
+    }
+
+    /// Build the graph of a block. Its bottom nodes are the reduced nodes of the block, in the same order as
+    /// [`Reduction::blocks`], and start at the current position of their first merged node.
+    /// Only the top nodes adjacent to the block are kept, in the same order.
+    pub fn block_graph(&self, graph: &AbscissaGraph, block: usize) -> AbscissaGraph {
+        bottom_subgraph(self.blocks[block].iter().map(|&u| {
+            let first = self.classes[u][0];
+            (&self.neighbors[u][..], graph.bottom_nodes_abscissas[first])
+        }))
+    }
+
+    /// Forced pairs of the bottom nodes of a block graph, see [`Reduction::block_graph`]
+    pub fn block_precedence(&self, block: usize) -> Precedence {
+        let block_neighbors: Vec<Vec<WeightedRank>> = self.blocks[block]
+            .iter()
+            .map(|&u| self.neighbors[u].clone())
+            .collect();
+        Precedence::from_neighbors(&block_neighbors)
+    }
+
+    /// Lift the solutions of the blocks into a full bottom order of the original graph.
+    /// `block_orders` contains the local bottom order of each block graph, see [`AbscissaGraph::bottom_order`].
+    pub fn lift(&self, block_orders: &[Vec<usize>]) -> Vec<usize> {
+        let mut order = Vec::with_capacity(
+            self.classes.iter().map(Vec::len).sum::<usize>() + self.isolated.len(),
+        );
+
+        for (block, block_order) in self.blocks.iter().zip(block_orders) {
+            for &local in block_order {
+                order.extend(&self.classes[block[local]]);
+            }
+        }
+        order.extend(&self.isolated);
+
+        order
+    }
+}
+
+/// Reduce the bottom layer of a graph, solve each block of the reduced instance, then lift the solution back.
+/// Each block graph starts from an order respecting its forced pairs, which are given to `solve_block`
+/// as precedence constraints: its solution must respect them too.
+/// Blocks with a single reduced node are already solved. The solution is optimal if all block solutions are.
+/// The size of the reduced instance is sent to the observer.
+pub fn solve_reduced<F>(
+    graph: &mut AbscissaGraph,
+    observer: &EventObserver,
+    mut solve_block: F,
+) -> ProofStatus
+where
+    F: FnMut(&mut AbscissaGraph, Precedence) -> ProofStatus,
+{
+    let reduction = Reduction::new(graph);
+    observer.notify(SolverEvent::Reduced {
+        bottom_nodes: graph.bottom_nodes_abscissas.len(),
+        reduced_nodes: reduction.node_count(),
+        blocks: reduction.blocks().len(),
+    });
+
+    let mut status = ProofStatus::Optimal;
+    let mut block_orders = Vec::with_capacity(reduction.blocks().len());
+    for block in 0..reduction.blocks().len() {
+        let mut block_graph = reduction.block_graph(graph, block);
+        if reduction.blocks()[block].len() > 1 {
+            let precedence = reduction.block_precedence(block);
+            block_graph.set_bottom_order(&precedence.linear_extension(&block_graph.bottom_order()));
+            if solve_block(&mut block_graph, precedence) != ProofStatus::Optimal {
+                status = ProofStatus::Feasible;
+            }
+        }
+        block_orders.push(block_graph.bottom_order());
+    }
+
+    graph.set_bottom_order(&reduction.lift(&block_orders));
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use clap::ValueEnum;
+    use ocm_parser::parse_file;
+    use walkdir::WalkDir;
+
+    use super::*;
+    use crate::{
+        algorithms::{solve_in_place, Algorithm, SolveOptions},
+        crossing_matrix::{CrossingMatrix, DenseCrossingMatrix},
+        crossings::count_crossings,
+    };
+
+    #[test]
+    fn reduction_preserves_optimum() {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let filename = file.path().to_str().unwrap();
+            let graph: AbscissaGraph = (&parse_file(filename).unwrap()).into();
+
+            let mut exact = graph.clone();
+            solve_in_place(&mut exact, &Algorithm::ExactDp, &SolveOptions::default());
+
+            let reduce_options = SolveOptions {
+                reduce: true,
+                ..Default::default()
+            };
+            let mut reduced = graph.clone();
+            let status = solve_in_place(&mut reduced, &Algorithm::ExactDp, &reduce_options).status;
+
+            assert_eq!(status, ProofStatus::Optimal, "{}", filename);
+            assert_eq!(
+                count_crossings(&reduced),
+                count_crossings(&exact),
+                "{}",
+                filename
+            );
+        }
+    }
+
+    #[test]
+    fn lifted_order_is_a_permutation() {
+        let graph: AbscissaGraph = (&parse_file("../datasets/medium/58.gr").unwrap()).into();
+        let options = SolveOptions {
+            reduce: true,
+            ..Default::default()
+        };
+
+        let mut reduced = graph.clone();
+        solve_in_place(&mut reduced, &Algorithm::IterBarycenter, &options);
+
+        let mut order = reduced.bottom_order();
+        order.sort_unstable();
+        assert_eq!(
+            order,
+            (0..graph.bottom_nodes_abscissas.len()).collect::<Vec<_>>()
+        );
+        assert!(Reduction::new(&graph).node_count() <= graph.bottom_nodes_abscissas.len());
+    }
+
+    #[test]
+    fn no_algorithm_violates_a_forced_pair() {
+        let files = WalkDir::new("../datasets/tiny")
+            .into_iter()
+            .chain(WalkDir::new("../datasets/medium/58.gr"))
+            .filter_map(Result::ok)
+            .filter(|e| e.path().is_file());
+
+        for file in files {
+            let filename = file.path().to_str().unwrap();
+            let mut graph: AbscissaGraph = (&parse_file(filename).unwrap()).into();
+            // Start from the reversed order, which violates the forced pairs of the sorted graphs
+            let reversed: Vec<usize> = graph.bottom_order().into_iter().rev().collect();
+            graph.set_bottom_order(&reversed);
+            let matrix = DenseCrossingMatrix::new(&graph);
+
+            for algorithm in Algorithm::value_variants() {
+                let options = SolveOptions {
+                    reduce: true,
+                    time_limit: Some(Duration::from_millis(50)),
+                    ..Default::default()
+                };
+                let mut solved = graph.clone();
+                solve_in_place(&mut solved, algorithm, &options);
+
+                let order = solved.bottom_order();
+                for (i, &v) in order.iter().enumerate() {
+                    for &u in &order[i + 1..] {
+                        assert!(
+                            matrix.crossings(u, v) > 0 || matrix.crossings(v, u) == 0,
+                            "{}: {} placed {} after {}",
+                            filename,
+                            algorithm,
+                            u,
+                            v
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Generic interface of the algorithms, so that new ones can be plugged in without editing this crate.
+//!
+//! A [`Solver`] reorders the free layer(s) of a graph from its current order, with its own configuration.
+//! The generic pipeline of [`crate::algorithms::solve_in_place`] (reduction, decomposition, refinement)
+//! works with any solver, and solvers can be looked up by name in a [`SolverRegistry`].
+
+use std::{collections::BTreeMap, fmt::Debug, sync::Arc};
+
+use clap::ValueEnum;
+
+use crate::{
+    algorithms::{Algorithm, ProofStatus, SolveOptions},
+    graphs::abscissa_graph::AbscissaGraph,
+};
+
+/// Algorithm reordering the free layer(s) of a graph
+pub trait Solver: Debug + Send + Sync {
+    /// Name of the algorithm, also used as the directory name of the analytics output
+    fn name(&self) -> &str;
+
+    /// Configuration of the algorithm, as `(name, value)` pairs
+    fn parameters(&self) -> Vec<(&'static str, String)> {
+        Vec::new()
+    }
+
+    /// Reorder the free layer(s) of a graph in place, starting from its current order.
+    /// The solution node order can be read back from the abscissas, see [`AbscissaGraph::bottom_order`].
+    fn solve(&self, graph: &mut AbscissaGraph, options: &SolveOptions) -> SolverStats;
+}
+
+/// Statistics reported by a solver
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SolverStats {
+    /// Whether the solution is proven to be optimal
+    pub status: ProofStatus,
+    /// Number of iterations of the algorithm: heuristic iterations, search nodes, local search passes or
+    /// annealing temperature steps (1 for the single pass algorithms), summed over the solved layers
+    pub iterations: u64,
+}
+
+impl SolverStats {
+    /// Statistics of a single pass heuristic
+    pub fn heuristic() -> Self {
+        SolverStats {
+            status: ProofStatus::Feasible,
+            iterations: 1,
+        }
+    }
+}
+
+/// Solvers indexed by name
+#[derive(Debug, Clone, Default)]
+pub struct SolverRegistry {
+    solvers: BTreeMap<String, Arc<dyn Solver>>,
+}
+
+impl SolverRegistry {
+    /// Empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry of all the built-in algorithms, with their default configuration
+    pub fn with_builtin_solvers() -> Self {
+        let mut registry = Self::new();
+        for algorithm in Algorithm::value_variants() {
+            registry.register(algorithm.solver());
+        }
+        registry
+    }
+
+    /// Register a solver under its name. Returns the solver previously registered with the same name, if any
+    pub fn register(&mut self, solver: Arc<dyn Solver>) -> Option<Arc<dyn Solver>> {
+        self.solvers.insert(solver.name().to_string(), solver)
+    }
+
+    /// Solver registered under a name
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Solver>> {
+        self.solvers.get(name).cloned()
+    }
+
+    /// Names of the registered solvers, in alphabetical order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.solvers.keys().map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ocm_parser::parse_file;
+
+    use super::*;
+    use crate::algorithms::solve_in_place;
+
+    /// Downstream algorithm: sort the bottom nodes by the rank of their leftmost neighbor
+    #[derive(Debug)]
+    struct LeftmostNeighbor;
+
+    impl Solver for LeftmostNeighbor {
+        fn name(&self) -> &str {
+            "leftmost_neighbor"
+        }
+
+        fn solve(&self, graph: &mut AbscissaGraph, _options: &SolveOptions) -> SolverStats {
+            let neighbors = graph.bottom_neighbors();
+            let mut order = graph.bottom_order();
+            order.sort_by_key(|&u| neighbors[u].first().map_or(u64::MAX, |(rank, _)| *rank));
+            graph.set_bottom_order(&order);
+            SolverStats::heuristic()
+        }
+    }
+
+    #[test]
+    fn registry_contains_builtin_solvers() {
+        let registry = SolverRegistry::with_builtin_solvers();
+        for algorithm in Algorithm::value_variants() {
+            let solver = registry.get(&algorithm.to_string()).unwrap();
+            assert_eq!(solver.name(), algorithm.to_string());
+        }
+        assert_eq!(registry.names().count(), Algorithm::value_variants().len());
+    }
+
+    #[test]
+    fn custom_solvers_run_in_the_pipeline() {
+        let mut registry = SolverRegistry::with_builtin_solvers();
+        assert!(registry.register(Arc::new(LeftmostNeighbor)).is_none());
+        let solver = registry.get("leftmost_neighbor").unwrap();
+
+        let graph: AbscissaGraph = (&parse_file("../datasets/tiny/website_20.gr").unwrap()).into();
+        let options = SolveOptions {
+            reduce: true,
+            ..Default::default()
+        };
+        let mut solution = graph.clone();
+        let stats = solve_in_place(&mut solution, &*solver, &options);
+        assert_eq!(stats.status, ProofStatus::Feasible);
+
+        let mut order = solution.bottom_order();
+        order.sort_unstable();
+        assert_eq!(
+            order,
+            (0..graph.bottom_nodes_abscissas.len()).collect::<Vec<_>>()
+        );
+    }
+}